@@ -1,6 +0,0 @@
-#!/bin/bash
-set -e
-BASE_DIR="/Users/mahmoudabdelkawy/.gemini/antigravity/scratch/nova_container_system/src"
-cd "$BASE_DIR"
-rm -rf builder cli.rs dashboard_api network.rs runtime.rs runtime_detector.rs runtimes scheduler.rs
-ls -la
@@ -5,9 +5,11 @@
 use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
 
-fn handle_client(mut stream: TcpStream) {
+fn handle_client(mut stream: TcpStream) -> std::io::Result<()> {
     let mut buffer = [0; 1024];
-    stream.read(&mut buffer).unwrap();
+    let n = stream.read(&mut buffer)?;
+    let request = String::from_utf8_lossy(&buffer[..n]);
+    println!("{}", request.lines().next().unwrap_or_default());
 
     let response = "HTTP/1.1 200 OK\r\n\
                    Content-Type: text/html\r\n\
@@ -21,8 +23,8 @@ fn handle_client(mut stream: TcpStream) {
                    </body>\
                    </html>";
 
-    stream.write(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
 }
 
 fn main() {
@@ -32,7 +34,9 @@ fn main() {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                handle_client(stream);
+                if let Err(e) = handle_client(stream) {
+                    eprintln!("Error: {}", e);
+                }
             }
             Err(e) => {
                 eprintln!("Error: {}", e);
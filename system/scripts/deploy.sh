@@ -65,7 +65,7 @@ echo "   https://github.com/$GITHUB_USER/nova-container-system/releases/new"
 echo ""
 echo "2. Build and upload binary:"
 echo "   cargo build --release"
-echo "   # Upload: target/release/nova_container_system"
+echo "   # Upload: target/release/nova"
 echo ""
 echo "3. Deploy website:"
 echo "   cd ../nova_website_bootstrap"
@@ -11,4 +11,4 @@ cargo build --release --quiet
 # Start Server
 echo "🚀 Starting Nova Cloud Dashboard..."
 echo "👉 Open: http://localhost:3000/login.html"
-./target/release/nova_container_system dashboard --port 3000
+./target/release/nova dashboard --port 3000
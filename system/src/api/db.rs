@@ -2,6 +2,9 @@ use anyhow::Result;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
+use crate::core::logging::LogRecord;
+use crate::core::metrics::RunReport;
+use crate::core::secrets::{self, SecretValue};
 
 pub type Db = Arc<Mutex<Connection>>;
 
@@ -42,6 +45,28 @@ pub struct Log {
     pub level: String,
     pub message: String,
     pub timestamp: String,
+    pub pid: Option<u32>,
+    pub container: Option<String>,
+}
+
+/// Secret listing entry; values are never returned by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSecret {
+    pub name: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentMetrics {
+    pub project_id: String,
+    pub deployment_id: String,
+    pub pid: u32,
+    pub fuel_consumed: u64,
+    pub instructions_per_sec: u64,
+    pub peak_memory_bytes: u64,
+    pub elapsed_ms: u64,
+    pub finished: bool,
+    pub recorded_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +87,11 @@ pub fn init_db() -> Result<Db> {
     // Read and execute schema
     let schema = include_str!("schema.sql");
     conn.execute_batch(schema)?;
+
+    // Databases created before guest logging lack these columns;
+    // the ALTER fails harmlessly when they already exist.
+    let _ = conn.execute("ALTER TABLE logs ADD COLUMN pid INTEGER", []);
+    let _ = conn.execute("ALTER TABLE logs ADD COLUMN container TEXT", []);
     
     Ok(Arc::new(Mutex::new(conn)))
 }
@@ -169,11 +199,10 @@ pub fn create_project(
     name: &str,
     status: &str,
     commit_hash: &str,
-    updated_at: &str,
     language: &str,
 ) -> Result<String> {
     let conn = db.lock().unwrap();
-    let id = format!("p_{}", uuid::Uuid::new_v4().to_string()[..8].to_string());
+    let id = format!("p_{}", &uuid::Uuid::new_v4().to_string()[..8]);
     
     let now = chrono::Utc::now().to_rfc3339();
     conn.execute(
@@ -198,7 +227,7 @@ pub fn update_project_status(db: &Db, project_id: &str, new_status: &str) -> Res
 /// Create a new deployment record
 pub fn create_deployment(db: &Db, project_id: &str, status: &str) -> Result<String> {
     let conn = db.lock().unwrap();
-    let id = format!("d_{}", uuid::Uuid::new_v4().to_string()[..8].to_string());
+    let id = format!("d_{}", &uuid::Uuid::new_v4().to_string()[..8]);
     conn.execute(
         "INSERT INTO deployments (id, project_id, status) VALUES (?1, ?2, ?3)",
         params![id, project_id, status],
@@ -247,11 +276,77 @@ pub fn add_log(db: &Db, project_id: &str, deployment_id: &str, level: &str, mess
     Ok(())
 }
 
+/// Add a structured log record emitted by a guest through `nova_log`
+pub fn add_guest_log(db: &Db, record: &LogRecord) -> Result<()> {
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "INSERT INTO logs (project_id, deployment_id, level, message, pid, container) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            record.project.as_deref().unwrap_or(""),
+            record.deployment.as_deref().unwrap_or(""),
+            record.level.as_str(),
+            record.message,
+            record.pid,
+            record.container,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store a resource usage report from a deployed instance
+pub fn add_run_report(db: &Db, report: &RunReport) -> Result<()> {
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "INSERT INTO deployment_metrics (project_id, deployment_id, pid, fuel_consumed, instructions_per_sec, peak_memory_bytes, elapsed_ms, finished)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            report.project.as_deref().unwrap_or(""),
+            report.deployment.as_deref().unwrap_or(""),
+            report.pid,
+            report.fuel_consumed as i64,
+            report.instructions_per_sec as i64,
+            report.peak_memory_bytes as i64,
+            report.elapsed_ms as i64,
+            report.finished,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Latest usage report of every deployed instance in the user's projects
+pub fn get_deployment_metrics(db: &Db, user_id: i64) -> Result<Vec<DeploymentMetrics>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT project_id, deployment_id, pid, fuel_consumed, instructions_per_sec, peak_memory_bytes, elapsed_ms, finished, recorded_at
+         FROM deployment_metrics
+         WHERE id IN (SELECT MAX(id) FROM deployment_metrics GROUP BY deployment_id, pid)
+           AND project_id IN (SELECT id FROM projects WHERE user_id = ?1)
+         ORDER BY recorded_at DESC"
+    )?;
+
+    let metrics = stmt.query_map(params![user_id], |row| {
+        Ok(DeploymentMetrics {
+            project_id: row.get(0)?,
+            deployment_id: row.get(1)?,
+            pid: row.get(2)?,
+            fuel_consumed: row.get::<_, i64>(3)? as u64,
+            instructions_per_sec: row.get::<_, i64>(4)? as u64,
+            peak_memory_bytes: row.get::<_, i64>(5)? as u64,
+            elapsed_ms: row.get::<_, i64>(6)? as u64,
+            finished: row.get(7)?,
+            recorded_at: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(metrics)
+}
+
 /// Get logs for a project
 pub fn get_project_logs(db: &Db, project_id: &str) -> Result<Vec<Log>> {
     let conn = db.lock().unwrap();
     let mut stmt = conn.prepare(
-        "SELECT id, project_id, deployment_id, level, message, timestamp FROM logs WHERE project_id = ?1 ORDER BY timestamp ASC"
+        "SELECT id, project_id, deployment_id, level, message, timestamp, pid, container FROM logs WHERE project_id = ?1 ORDER BY timestamp ASC"
     )?;
     
     let logs = stmt.query_map(params![project_id], |row| {
@@ -262,6 +357,8 @@ pub fn get_project_logs(db: &Db, project_id: &str) -> Result<Vec<Log>> {
             level: row.get(3)?,
             message: row.get(4)?,
             timestamp: row.get(5)?,
+            pid: row.get(6)?,
+            container: row.get(7)?,
         })
     })?
     .collect::<Result<Vec<_>, _>>()?;
@@ -277,6 +374,76 @@ pub fn get_project_id_by_name(db: &Db, name: &str) -> Result<Option<String>> {
     Ok(id)
 }
 
+/// Get the ID of the user who owns a project
+pub fn get_project_owner(db: &Db, project_id: &str) -> Result<Option<i64>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT user_id FROM projects WHERE id = ?1")?;
+    let owner = stmt.query_row(params![project_id], |row| row.get(0)).optional()?;
+    Ok(owner)
+}
+
+/// The key shared with the local `nova secret` store
+fn secret_key() -> Result<chacha20poly1305::Key> {
+    secrets::load_or_create_key(&secrets::default_dir())
+}
+
+/// Create or update a project secret (encrypted before it is stored)
+pub fn set_project_secret(db: &Db, project_id: &str, name: &str, value: &str) -> Result<()> {
+    secrets::validate_name(name)?;
+    let encrypted = secrets::encrypt(&secret_key()?, value)?;
+    let conn = db.lock().unwrap();
+    conn.execute(
+        "INSERT INTO project_secrets (project_id, name, value, updated_at) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)
+         ON CONFLICT(project_id, name) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![project_id, name, encrypted],
+    )?;
+    Ok(())
+}
+
+/// List secret names for a project
+pub fn list_project_secrets(db: &Db, project_id: &str) -> Result<Vec<ProjectSecret>> {
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT name, updated_at FROM project_secrets WHERE project_id = ?1 ORDER BY name ASC"
+    )?;
+
+    let secrets = stmt.query_map(params![project_id], |row| {
+        Ok(ProjectSecret {
+            name: row.get(0)?,
+            updated_at: row.get(1)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(secrets)
+}
+
+/// Delete a project secret, returning whether it existed
+pub fn delete_project_secret(db: &Db, project_id: &str, name: &str) -> Result<bool> {
+    let conn = db.lock().unwrap();
+    let deleted = conn.execute(
+        "DELETE FROM project_secrets WHERE project_id = ?1 AND name = ?2",
+        params![project_id, name],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Decrypt all secrets of a project for injection at container start
+pub fn resolve_project_secrets(db: &Db, project_id: &str) -> Result<Vec<SecretValue>> {
+    let key = secret_key()?;
+    let conn = db.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT name, value FROM project_secrets WHERE project_id = ?1")?;
+
+    let rows = stmt.query_map(params![project_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(|(name, encrypted)| Ok(SecretValue { value: secrets::decrypt(&key, &encrypted)?, name }))
+        .collect()
+}
+
 /// Get system stats (real metrics)
 pub fn get_stats(_db: &Db) -> SystemStats {
     use sysinfo::{System, Disks};
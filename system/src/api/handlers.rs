@@ -4,7 +4,6 @@ use axum::{
     response::{IntoResponse, sse::{Event, Sse}},
     Json,
 };
-use std::io::Write;
 use std::convert::Infallible;
 // No longer using Stream/StreamExt directly in this file as of last refactor
 use serde::{Deserialize, Serialize};
@@ -39,6 +38,7 @@ pub struct SignupResponse {
 }
 
 #[derive(Deserialize)]
+#[allow(dead_code)]
 pub struct DeployRequest {
     project_name: String,
     language: Option<String>,
@@ -70,6 +70,12 @@ pub struct BillingResponse {
     pub history: Vec<UsageRecord>,
 }
 
+#[derive(Deserialize)]
+pub struct SetSecretRequest {
+    pub name: String,
+    pub value: String,
+}
+
 #[derive(Deserialize)]
 pub struct TokenQuery {
     pub token: Option<String>,
@@ -419,7 +425,6 @@ pub async fn deploy_project(
         &project_name,
         "deploying", // initial state
         "7a2b9d1", // mock commit
-        "", // db.rs will set current time
         &language.clone().unwrap_or_else(|| "node".to_string()),
     ) {
         Ok(i) => i,
@@ -473,7 +478,32 @@ pub async fn deploy_project(
         ];
         let _ = std::fs::write(&fake_wasm_path, dummy_wasm);
         
-        match crate::core::scheduler::Scheduler::run_replicas(fake_wasm_path, 1, 100_000, Some(256), None).await {
+        // Guest records written through nova_log land in the same logs table
+        let log_db = db_clone.clone();
+        let mut config = crate::core::runtime::RunConfig::new(proj_name_clone.clone());
+        config.fuel = 100_000;
+        config.memory_limit_mb = Some(256);
+        config.project = Some(id_clone.clone());
+        config.deployment = Some(d_id_clone.clone());
+        config.log_sink = std::sync::Arc::new(move |record: &crate::core::logging::LogRecord| {
+            let _ = db::add_guest_log(&log_db, record);
+        });
+        // Fuel, throughput and peak memory are stored per deployment for quotas
+        let metrics_db = db_clone.clone();
+        config.metrics_sink = std::sync::Arc::new(move |report: &crate::core::metrics::RunReport| {
+            let _ = db::add_run_report(&metrics_db, report);
+        });
+        config.metrics_interval = Some(std::time::Duration::from_secs(10));
+        // Project secrets are decrypted only now, at container start
+        config.secrets = match db::resolve_project_secrets(&db_clone, &id_clone) {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                let _ = db::add_log(&db_clone, &id_clone, &d_id_clone, "ERROR", &format!("Failed to resolve project secrets: {}", e));
+                Vec::new()
+            }
+        };
+
+        match crate::core::scheduler::Scheduler::run_replicas(fake_wasm_path, 1, config).await {
             Ok(_) => {
                 let _ = db::update_project_status(&db_clone, &id_clone, "active");
                 let _ = db::update_deployment_status(&db_clone, &d_id_clone, "SUCCESS", None);
@@ -541,7 +571,7 @@ pub async fn get_billing(
         None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
     };
 
-    if let Err(_) = super::auth::validate_token(token) {
+    if super::auth::validate_token(token).is_err() {
         return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response();
     }
 
@@ -606,7 +636,7 @@ pub async fn control_project(
         None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
     };
 
-    if let Err(_) = super::auth::validate_token(token) {
+    if super::auth::validate_token(token).is_err() {
         return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response();
     }
 
@@ -640,7 +670,7 @@ pub async fn get_project_logs(
         }
     };
 
-    if let Err(_) = super::auth::validate_token(&token_str) {
+    if super::auth::validate_token(&token_str).is_err() {
         return (StatusCode::UNAUTHORIZED, "Invalid token").into_response();
     }
 
@@ -655,7 +685,10 @@ pub async fn get_project_logs(
         let mock_project_id = "p_1"; // Default for demo
         if let Ok(logs) = db::get_project_logs(&db_clone, mock_project_id) {
             for log in logs {
-                let formatted = format!("[{}] [{}] {}", log.timestamp, log.level, log.message);
+                let formatted = match (&log.container, log.pid) {
+                    (Some(container), Some(pid)) => format!("[{}] [{}] [{}#{}] {}", log.timestamp, log.level, container, pid, log.message),
+                    _ => format!("[{}] [{}] {}", log.timestamp, log.level, log.message),
+                };
                 yield Ok::<_, Infallible>(Event::default().data(formatted));
             }
         }
@@ -679,6 +712,117 @@ pub async fn get_project_logs(
         .into_response()
 }
 
+/// Resolve a project ID or name, refusing projects that `user_id` does not own
+fn owned_project_id(db: &Db, project_id: &str, user_id: i64) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let actual_id = match db::get_project_id_by_name(db, project_id) {
+        Ok(Some(id)) => id,
+        _ => project_id.to_string(),
+    };
+
+    match db::get_project_owner(db, &actual_id) {
+        Ok(Some(owner)) if owner == user_id => Ok(actual_id),
+        Ok(Some(_)) => Err((StatusCode::FORBIDDEN, Json(serde_json::json!({"error": "Forbidden"})))),
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Project not found"})))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()})))),
+    }
+}
+
+/// GET /api/projects/:id/secrets - List secret names for a project
+pub async fn get_project_secrets(
+    State(db): State<Db>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Path(project_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    let token = match headers.get("Authorization") {
+        Some(value) => match value.to_str() {
+            Ok(v) if v.starts_with("Bearer ") => &v[7..],
+            _ => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Unauthorized"}))).into_response(),
+        },
+        None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
+    };
+
+    let claims = match super::auth::validate_token(token) {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response(),
+    };
+
+    let actual_id = match owned_project_id(&db, &project_id, claims.user_id) {
+        Ok(id) => id,
+        Err(response) => return response.into_response(),
+    };
+
+    match db::list_project_secrets(&db, &actual_id) {
+        Ok(secrets) => (StatusCode::OK, Json(secrets)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+/// POST /api/projects/:id/secrets - Create or update a project secret
+pub async fn set_project_secret(
+    State(db): State<Db>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Path(project_id): axum::extract::Path<String>,
+    Json(payload): Json<SetSecretRequest>,
+) -> impl IntoResponse {
+    let token = match headers.get("Authorization") {
+        Some(value) => match value.to_str() {
+            Ok(v) if v.starts_with("Bearer ") => &v[7..],
+            _ => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Unauthorized"}))).into_response(),
+        },
+        None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
+    };
+
+    let claims = match super::auth::validate_token(token) {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response(),
+    };
+
+    if let Err(e) = crate::core::secrets::validate_name(&payload.name) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e.to_string()}))).into_response();
+    }
+
+    let actual_id = match owned_project_id(&db, &project_id, claims.user_id) {
+        Ok(id) => id,
+        Err(response) => return response.into_response(),
+    };
+
+    match db::set_project_secret(&db, &actual_id, &payload.name, &payload.value) {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({"success": true}))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+/// DELETE /api/projects/:id/secrets/:name - Remove a project secret
+pub async fn delete_project_secret(
+    State(db): State<Db>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Path((project_id, name)): axum::extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    let token = match headers.get("Authorization") {
+        Some(value) => match value.to_str() {
+            Ok(v) if v.starts_with("Bearer ") => &v[7..],
+            _ => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Unauthorized"}))).into_response(),
+        },
+        None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
+    };
+
+    let claims = match super::auth::validate_token(token) {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response(),
+    };
+
+    let actual_id = match owned_project_id(&db, &project_id, claims.user_id) {
+        Ok(id) => id,
+        Err(response) => return response.into_response(),
+    };
+
+    match db::delete_project_secret(&db, &actual_id, &name) {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({"success": true}))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Secret not found"}))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
 /// GET /api/projects/:id/deployments - Get deployment history for a project
 pub async fn get_deployment_history(
     State(db): State<Db>,
@@ -694,7 +838,7 @@ pub async fn get_deployment_history(
         None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
     };
 
-    if let Err(_) = super::auth::validate_token(token) {
+    if super::auth::validate_token(token).is_err() {
         return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response();
     }
 
@@ -716,16 +860,23 @@ pub async fn get_metrics(
     State(db): State<Db>,
     headers: axum::http::HeaderMap,
 ) -> impl IntoResponse {
-    // Attempt auth but allow through for now (can be tightened later)
-    let authed = headers.get("Authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .map(|t| super::auth::validate_token(t).is_ok())
-        .unwrap_or(false);
-
-    // Gather stats from DB
+    let token = match headers.get("Authorization") {
+        Some(value) => match value.to_str() {
+            Ok(v) if v.starts_with("Bearer ") => &v[7..],
+            _ => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Unauthorized"}))).into_response(),
+        },
+        None => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Missing token"}))).into_response(),
+    };
+
+    let claims = match super::auth::validate_token(token) {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Invalid token"}))).into_response(),
+    };
+
+    // Gather stats from DB; per-deployment usage only for the caller's own projects
     let project_count = db::count_projects(&db).unwrap_or(0);
     let user_count    = db::count_users(&db).unwrap_or(0);
+    let deployments   = db::get_deployment_metrics(&db, claims.user_id).unwrap_or_default();
 
     // Simulated live counters (in production these would be real Prometheus counters)
     let uptime_secs: u64  = 3600 * 24 + 187; // ~1 day uptime
@@ -749,12 +900,13 @@ pub async fn get_metrics(
             "nova_active_projects":       project_count,
             "nova_total_users":           user_count,
             "nova_edge_nodes_online":     42,
+            "deployments":                deployments,
             "collected_at":               chrono::Utc::now().to_rfc3339(),
         });
         (StatusCode::OK, [("content-type", "application/json")], serde_json::to_string_pretty(&json).unwrap_or_default()).into_response()
     } else {
         // Prometheus text format
-        let body = format!(
+        let mut body = format!(
 r#"# HELP nova_edge_uptime_seconds Total uptime of the Nova edge daemon in seconds.
 # TYPE nova_edge_uptime_seconds counter
 nova_edge_uptime_seconds {uptime_secs}
@@ -792,6 +944,20 @@ nova_total_users {user_count}
 nova_edge_nodes_online 42
 "#
         );
+        if !deployments.is_empty() {
+            body.push_str("\n# HELP nova_deployment_fuel_consumed Wasm fuel (instructions) consumed per instance.\n# TYPE nova_deployment_fuel_consumed gauge\n");
+            for m in &deployments {
+                body.push_str(&format!("nova_deployment_fuel_consumed{{project=\"{}\",deployment=\"{}\",pid=\"{}\"}} {}\n", m.project_id, m.deployment_id, m.pid, m.fuel_consumed));
+            }
+            body.push_str("\n# HELP nova_deployment_instructions_per_sec Wasm instructions executed per second per instance.\n# TYPE nova_deployment_instructions_per_sec gauge\n");
+            for m in &deployments {
+                body.push_str(&format!("nova_deployment_instructions_per_sec{{project=\"{}\",deployment=\"{}\",pid=\"{}\"}} {}\n", m.project_id, m.deployment_id, m.pid, m.instructions_per_sec));
+            }
+            body.push_str("\n# HELP nova_deployment_peak_memory_bytes Peak linear memory per instance.\n# TYPE nova_deployment_peak_memory_bytes gauge\n");
+            for m in &deployments {
+                body.push_str(&format!("nova_deployment_peak_memory_bytes{{project=\"{}\",deployment=\"{}\",pid=\"{}\"}} {}\n", m.project_id, m.deployment_id, m.pid, m.peak_memory_bytes));
+            }
+        }
         (StatusCode::OK, [("content-type", "text/plain; version=0.0.4")], body).into_response()
     }
 }
@@ -29,6 +29,8 @@ pub async fn start_server(port: u16) {
         .route("/api/deployments/logs", get(handlers::get_project_logs))
         .route("/api/deployments/control", post(handlers::control_project))
         .route("/api/projects/:id/deployments", get(handlers::get_deployment_history))
+        .route("/api/projects/:id/secrets", get(handlers::get_project_secrets).post(handlers::set_project_secret))
+        .route("/api/projects/:id/secrets/:name", axum::routing::delete(handlers::delete_project_secret))
         .route("/api/metrics", get(handlers::get_metrics))
         // Serve Static Files (Frontend)
         .nest_service("/", ServeDir::new("www"))
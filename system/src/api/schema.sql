@@ -48,10 +48,39 @@ CREATE TABLE IF NOT EXISTS logs (
     deployment_id TEXT NOT NULL,
     level TEXT NOT NULL, -- 'INFO', 'ERROR', 'SUCCESS'
     message TEXT NOT NULL,
+    pid INTEGER, -- set for guest records written through nova_log
+    container TEXT,
     timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
     FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
 );
 
+-- Per-project secrets, encrypted with the local secret key.
+-- Values are only decrypted when a deployment starts.
+CREATE TABLE IF NOT EXISTS project_secrets (
+    project_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (project_id, name),
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+-- Resource usage reports from deployed instances: periodic while running,
+-- plus a final one (finished = 1) when the instance exits.
+CREATE TABLE IF NOT EXISTS deployment_metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id TEXT NOT NULL,
+    deployment_id TEXT NOT NULL,
+    pid INTEGER NOT NULL,
+    fuel_consumed INTEGER NOT NULL,
+    instructions_per_sec INTEGER NOT NULL,
+    peak_memory_bytes INTEGER NOT NULL,
+    elapsed_ms INTEGER NOT NULL,
+    finished INTEGER NOT NULL,
+    recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
 -- Insert demo account (password: demo123)
 -- Password hash generated with bcrypt for 'demo123'
 INSERT OR IGNORE INTO users (id, email, password_hash, name, billing_tier, api_key, created_at)
@@ -1,14 +1,19 @@
 use clap::{Parser, Subcommand};
-use anyhow::Result;
-use std::path::PathBuf;
-use crate::core::runtime_detector::{RuntimeDetector, Language};
-use crate::core::runtimes::{PythonRuntime, NodeJSRuntime, StaticServer, Runtime, ResourceLimits};
-use crate::core::scheduler;
-use crate::core::runtime;
-use crate::core::builder;
-use crate::api;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use nova::core::runtime_detector::{RuntimeDetector, Language};
+use nova::core::runtimes::{PythonRuntime, NodeJSRuntime, StaticServer, Runtime, ResourceLimits};
+use nova::core::scheduler;
+use nova::core::runtime;
+use nova::core::builder;
+use nova::core::capabilities;
+use nova::core::novaignore::NovaIgnore;
+use nova::core::http::{HttpPolicy, HttpRule};
+use nova::core::secrets::SecretStore;
+use nova::core::trace::{TraceHandle, TraceHeader};
+use nova::core::snapshot::{self, CheckpointRequest, Snapshot};
+use nova::api;
 use std::io::{Read, Write};
-use walkdir::WalkDir;
 use zip::write::FileOptions;
 
 #[derive(Parser)]
@@ -41,8 +46,146 @@ pub enum Commands {
         /// Map host directory: host_path:guest_path
         #[arg(long)]
         map_dir: Option<String>,
+
+        /// Container name used to tag logs (defaults to the file name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Allow outbound HTTP: host[:port][=METHOD,...] (repeatable, default method GET)
+        #[arg(long = "allow-http")]
+        allow_http: Vec<String>,
+
+        /// Outbound HTTP request timeout in seconds
+        #[arg(long, default_value_t = 10)]
+        http_timeout: u64,
+
+        /// Maximum outbound HTTP response size in bytes
+        #[arg(long, default_value_t = 1024 * 1024)]
+        http_max_bytes: usize,
+
+        /// Inject a secret from `nova secret` (repeatable)
+        #[arg(long)]
+        secret: Vec<String>,
+
+        /// Expose secrets as read-only files under this guest path instead of env vars
+        #[arg(long)]
+        secrets_dir: Option<String>,
+
+        /// Reproducible run: virtual clocks, seeded randomness, ordered message delivery
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Seed for --deterministic
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Record host-call events (clocks, randomness, messages, HTTP) to a trace file
+        #[arg(long)]
+        record: Option<PathBuf>,
+
+        /// Sample the guest and write a Firefox-profiler-compatible profile
+        #[arg(long)]
+        profile: Option<PathBuf>,
+
+        /// Write /tmp/perf-<pid>.map so `perf` can symbolize Wasm functions
+        #[arg(long)]
+        perfmap: bool,
+
+        /// Also print a usage report every N seconds while running (one is always printed at exit)
+        #[arg(long, value_name = "SECS")]
+        metrics_interval: Option<u64>,
+
+        /// Keep DWARF debug info for gdb/lldb and print source-level backtraces on traps
+        #[arg(long)]
+        debug: bool,
+
+        /// Accept `nova checkpoint` requests (the module must import nova_recv)
+        #[arg(long)]
+        checkpoint: bool,
+
+        /// Skip detection and use this runtime: wasm, python or node
+        #[arg(long)]
+        runtime: Option<String>,
+    },
+
+    /// Re-run a module against a trace from `nova run --record`
+    Replay {
+        /// Trace file
+        trace: PathBuf,
+
+        /// Module to replay (defaults to the recorded module path)
+        #[arg(long)]
+        module: Option<String>,
     },
     
+    /// Snapshot a running Wasm container at its next safe point
+    Checkpoint {
+        /// Container name
+        container: String,
+
+        /// Replica pid to checkpoint
+        #[arg(long, default_value_t = 0)]
+        pid: u32,
+
+        /// Snapshot file (default: <container>-<pid>.snap)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Keep the container running after the snapshot is written
+        #[arg(long)]
+        leave_running: bool,
+
+        /// Seconds to wait for the container to reach a safe point
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
+
+    /// Resume a container from a snapshot written by `nova checkpoint`
+    Restore {
+        /// Snapshot file
+        snapshot: PathBuf,
+
+        /// Module to resume (defaults to the checkpointed module path)
+        #[arg(long)]
+        module: Option<String>,
+
+        /// Container name (defaults to the checkpointed name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Inject a secret from `nova secret` in addition to the checkpointed ones (repeatable)
+        #[arg(long)]
+        secret: Vec<String>,
+
+        /// Accept `nova checkpoint` requests again after resuming
+        #[arg(long)]
+        checkpoint: bool,
+    },
+
+    /// Run a module's init export and bake the resulting state into a new module
+    Preinit {
+        /// Input module (.wasm or .wat)
+        input: PathBuf,
+
+        /// Export to run, e.g. one that parses config or builds lookup tables
+        #[arg(long)]
+        init: String,
+
+        /// Output module (default: <input>.preinit.wasm)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Validate a module and list its imports, exports, memories and custom sections
+    Inspect {
+        /// Module (.wasm or .wat) or image tag
+        target: String,
+
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Build a container image from a Novafile
     Build {
         /// Path to Novafile
@@ -56,6 +199,18 @@ pub enum Commands {
         /// Build context directory
         #[arg(long, default_value = ".")]
         context: PathBuf,
+
+        /// Run every step instead of reusing cached layers
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Value for an ARG: KEY=VALUE, or KEY to take it from the environment
+        #[arg(long = "build-arg", value_name = "KEY=VALUE")]
+        build_args: Vec<String>,
+
+        /// Let RUN steps execute host commands (they are not sandboxed)
+        #[arg(long)]
+        allow_host_run: bool,
     },
     
     /// Serve static files
@@ -118,6 +273,12 @@ pub enum Commands {
         name: String,
     },
 
+    /// Manage secrets injected into containers at start
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommands,
+    },
+
     /// Start the Cloud Dashboard Server
     Dashboard {
         /// Port to listen on
@@ -126,12 +287,118 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum SecretCommands {
+    /// Create or update a secret (reads the value from stdin without --value)
+    Set {
+        /// Secret name (also the env var name)
+        name: String,
+
+        /// Secret value
+        #[arg(long)]
+        value: Option<String>,
+    },
+
+    /// List secret names
+    Ls,
+
+    /// Remove a secret
+    Rm {
+        /// Secret name
+        name: String,
+    },
+}
+
 pub async fn execute() -> Result<()> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Run { path, replicas, fuel, memory, map_dir } => {
+        Commands::Run { path, replicas, fuel, memory, map_dir, name, allow_http, http_timeout, http_max_bytes, secret, secrets_dir, deterministic, seed, record, profile, perfmap, metrics_interval, debug, checkpoint, runtime: runtime_name } => {
             let path_buf = PathBuf::from(path);
+
+            let container = name.clone().unwrap_or_else(|| {
+                path_buf.file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "nova".to_string())
+            });
+
+            let mut config = runtime::RunConfig::new(container);
+            config.fuel = fuel.unwrap_or(u64::MAX);
+            config.memory_limit_mb = *memory;
+            config.map_dir = map_dir.clone();
+            config.http = HttpPolicy {
+                allow: allow_http.iter().map(|r| HttpRule::parse(r)).collect::<Result<_>>()?,
+                timeout: std::time::Duration::from_secs(*http_timeout),
+                max_response_bytes: *http_max_bytes,
+            };
+            config.secrets_dir = secrets_dir.clone();
+            config.profile = profile.clone();
+            config.perfmap = *perfmap;
+            config.debug = *debug;
+            config.checkpoint = *checkpoint;
+            config.metrics_interval = metrics_interval.map(std::time::Duration::from_secs);
+            if config.profile.is_some() && *replicas > 1 {
+                anyhow::bail!("--profile samples a single instance; drop --replicas");
+            }
+            if *deterministic {
+                config.deterministic = Some(*seed);
+                if !config.http.allow.is_empty() {
+                    eprintln!("⚠️ Outbound HTTP responses are not reproducible in deterministic mode");
+                }
+            }
+
+            // Image tags resolve against the local image store before language detection
+            let image = if path_buf.exists() { None } else { builder::Image::find(path)? };
+
+            let mut secret_names = secret.clone();
+            if let Some(image) = &image {
+                for name in &image.metadata.secrets {
+                    if !secret_names.contains(name) {
+                        secret_names.push(name.clone());
+                    }
+                }
+            }
+            if !secret_names.is_empty() {
+                config.secrets = SecretStore::open_default()?.resolve(&secret_names)?;
+            }
+
+            if let Some(image) = image {
+                let (module, args) = image.entry_module()?;
+                println!("📦 Image: {} ({})\n", image.metadata.tag, module.display());
+                config.args = args;
+                config.env = image.metadata.env.clone();
+                config.healthcheck = image.metadata.healthcheck.clone();
+
+                // Flags win over the image's RESOURCES
+                let resources = &image.metadata.resources;
+                if memory.is_none() {
+                    config.memory_limit_mb = resources.memory_mb;
+                }
+                if let (None, Some(image_fuel)) = (fuel, resources.fuel) {
+                    config.fuel = image_fuel;
+                }
+
+                // Volumes keep their files across runs with the same --name, or of the
+                // same image tag without one; --map-dir onto a volume's path replaces it
+                let mapped = map_dir.as_deref().and_then(|m| m.split_once(':')).map(|(_, guest)| guest);
+                let volume_owner = name.as_ref().unwrap_or(&image.metadata.tag);
+                let volumes_dir = std::env::current_dir()?
+                    .join(".nova/volumes")
+                    .join(volume_owner.replace(':', "_").replace('/', "-"));
+                for volume in &image.metadata.volumes {
+                    if mapped != Some(volume.as_str()) {
+                        config.volumes.push((volumes_dir.join(builder::layer::image_path(volume)?), volume.clone()));
+                    }
+                }
+                match &image.metadata.capabilities {
+                    Some(capabilities) => {
+                        capabilities.enforce(&mut config)?;
+                        println!("🛡️  Effective policy:\n{}\n", capabilities::effective_policy(&config));
+                    }
+                    None => eprintln!("⚠️ Image '{}' has no capability manifest; grants are not restricted\n", image.metadata.tag),
+                }
+                return run_wasm_container(&module.to_string_lossy(), *replicas, config, record.as_deref()).await;
+            }
             
             // Create resource limits
             let limits = ResourceLimits {
@@ -140,10 +407,17 @@ pub async fn execute() -> Result<()> {
                 fuel: *fuel,
             };
             
-            // Detect language/runtime
-            let detector = RuntimeDetector::new(&path_buf);
+            // Detect language/runtime unless --runtime says otherwise
+            let language = match runtime_name {
+                Some(name) => RuntimeDetector::parse_runtime(name)?,
+                None => RuntimeDetector::new(&path_buf).detect_language()?,
+            };
             
-            match detector.detect_language()? {
+            match language {
+                Language::Wasm => {
+                    println!("🔍 Detected: WebAssembly\n");
+                    run_wasm_container(path, *replicas, config, record.as_deref()).await?;
+                }
                 Language::Python => {
                     println!("🔍 Detected: Python\n");
                     let runtime = PythonRuntime::with_limits(limits);
@@ -164,27 +438,168 @@ pub async fn execute() -> Result<()> {
                     
                     runtime.run(&path_buf, &[])?;
                 }
-                Language::Rust | Language::Go | Language::Unknown => {
-                    // Fall back to Wasm runtime
-                    println!("🔍 Detected: WebAssembly\n");
-                    let fuel_val = fuel.unwrap_or(u64::MAX);
-                    
-                    if *replicas > 1 {
-                        scheduler::Scheduler::run_replicas(path.clone(), *replicas, fuel_val, *memory, map_dir.clone()).await?;
-                    } else {
-                        println!("🚀 Nova: Starting single container from '{}'...", path);
-                        runtime::run_wasm(path, 0, fuel_val, *memory, map_dir.clone()).await?;
-                    }
+                Language::Rust => {
+                    anyhow::bail!("Rust sources are not run directly. Build with `cargo build --target wasm32-wasi` and run the .wasm");
                 }
-                _ => {
-                    anyhow::bail!("Unsupported language. Supported: Python, Node.js, Rust (Wasm)");
+                Language::Go => {
+                    anyhow::bail!("Go sources are not run directly. Build with `GOOS=wasip1 GOARCH=wasm go build` and run the .wasm");
+                }
+                Language::Ruby | Language::PHP => {
+                    anyhow::bail!(
+                        "{:?} has no native runtime in Nova. Run it on an interpreter compiled to Wasm (ruby.wasm, php-wasm) with `nova run <interpreter.wasm> --map-dir <app>:/app`",
+                        language
+                    );
+                }
+                Language::Java | Language::CSharp => {
+                    anyhow::bail!(
+                        "{:?} has no native runtime in Nova. Compile it to a WASI module (TeaVM for Java, the .NET wasi-experimental workload for C#) and run the .wasm",
+                        language
+                    );
+                }
+                Language::Unknown => {
+                    anyhow::bail!("Could not detect how to run '{}'. Pass --runtime wasm, python or node", path);
                 }
             }
         }
         
-        Commands::Build { file, tag, context } => {
+        Commands::Replay { trace, module } => {
+            let (handle, header) = TraceHandle::replay(trace)?;
+            let module = module.clone().unwrap_or_else(|| header.module.clone());
+            println!("⏪ Replaying '{}' (recorded {}) against '{}'\n", trace.display(), header.recorded_at, module);
+
+            let container = PathBuf::from(&module).file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "nova".to_string());
+            let mut config = runtime::RunConfig::new(container);
+            config.pid = header.pid;
+            config.fuel = header.fuel;
+            config.memory_limit_mb = header.memory_limit_mb;
+            config.map_dir = header.map_dir.clone();
+            config.volumes = header.volumes.clone();
+            config.http = header.http.clone();
+            config.deterministic = header.deterministic;
+            config.args = header.args.clone();
+            config.env = header.env.clone();
+            config.secrets = SecretStore::open_default()?.resolve(&header.secrets)?;
+            config.secrets_dir = header.secrets_dir.clone();
+            config.trace = Some(handle.clone());
+
+            let result = runtime::run_wasm(&module, config).await;
+            handle.finish()?;
+            if let Err(e) = &result {
+                println!("   Guest exited with: {}", e);
+            }
+
+            let (events, divergence) = handle.report();
+            match divergence {
+                None => println!("✅ Replay matched the recording ({} events)", events),
+                Some(divergence) => anyhow::bail!("Replay diverged at {}", divergence),
+            }
+        }
+
+        Commands::Checkpoint { container, pid, output, leave_running, timeout } => {
+            let output = output.clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{}-{}.snap", container, pid)));
+            // The instance writes the file, possibly from another working directory
+            let output = std::env::current_dir()?.join(output);
+
+            println!("📸 Waiting for '{}' (pid {}) to reach a safe point...", container, pid);
+            let request = CheckpointRequest { output, stop: !*leave_running };
+            let path = snapshot::request_checkpoint(container, *pid, request, std::time::Duration::from_secs(*timeout)).await?;
+            println!("✅ Snapshot written to {}", path.display());
+            if !*leave_running {
+                println!("   Resume with: nova restore {}", path.display());
+            }
+        }
+
+        Commands::Restore { snapshot: snapshot_path, module, name, secret, checkpoint } => {
+            let snapshot = Snapshot::read(snapshot_path)?;
+            let header = &snapshot.header;
+            let module = module.clone().unwrap_or_else(|| header.module.clone());
+            println!("▶️  Restoring '{}' (checkpointed {}) from '{}'", header.container, header.created_at, module);
+
+            let mut config = runtime::RunConfig::new(name.clone().unwrap_or_else(|| header.container.clone()));
+            config.pid = header.pid;
+            config.fuel = header.fuel;
+            config.memory_limit_mb = header.memory_limit_mb;
+            config.map_dir = header.map_dir.clone();
+            config.volumes = header.volumes.clone();
+            config.args = header.args.clone();
+            config.env = header.env.clone();
+            let mut secret_names = header.secrets.clone();
+            secret_names.extend(secret.iter().filter(|name| !header.secrets.contains(name)).cloned());
+            if !secret_names.is_empty() {
+                config.secrets = SecretStore::open_default()?.resolve(&secret_names)?;
+            }
+            config.secrets_dir = header.secrets_dir.clone();
+            config.restore = Some(std::sync::Arc::new(snapshot));
+            config.checkpoint = *checkpoint;
+
+            runtime::run_wasm(&module, config).await?;
+        }
+
+        Commands::Preinit { input, init, output } => {
+            let output = output.clone().unwrap_or_else(|| input.with_extension("preinit.wasm"));
+            println!("⚡ Pre-initializing '{}' with '{}'", input.display(), init);
+
+            let wasm = runtime::read_module(input)?;
+            let initialized = nova::core::preinit::preinit(&wasm, init)?;
+            std::fs::write(&output, &initialized)?;
+            println!("✅ Wrote {} ({} bytes)", output.display(), initialized.len());
+        }
+
+        Commands::Inspect { target, json } => {
+            let path = if Path::new(target).exists() {
+                PathBuf::from(target)
+            } else {
+                match builder::Image::find(target)? {
+                    Some(image) => {
+                        if let Some(digest) = image.digest()? {
+                            image.verify(&builder::BlobStore::local())?;
+                            let layers = image.manifest.as_ref().map(|m| m.layers.len()).unwrap_or(0);
+                            if !*json {
+                                println!("📦 Image {} ({}, {} layers, all digests verified)\n", target, digest, layers);
+                            }
+                        }
+                        image.entry_module()?.0
+                    }
+                    None => anyhow::bail!("'{}' is neither a file nor a local image", target),
+                }
+            };
+
+            let wasm = runtime::read_module(&path)?;
+            let info = nova::core::inspect::inspect(&wasm, &[])
+                .with_context(|| format!("{} is not a valid module", path.display()))?;
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                print_module_info(&path, &info);
+            }
+            if !info.problems.is_empty() {
+                anyhow::bail!("{} cannot run under Nova ({} problem(s))", path.display(), info.problems.len());
+            }
+        }
+
+        Commands::Build { file, tag, context, no_cache, build_args, allow_host_run } => {
             println!("🔨 Building image from '{}'", file.display());
-            let builder = builder::ImageBuilder::new(file, context, tag.clone())?;
+            let mut args = std::collections::HashMap::new();
+            for arg in build_args {
+                match arg.split_once('=') {
+                    Some((key, value)) => {
+                        args.insert(key.to_string(), value.to_string());
+                    }
+                    None => {
+                        if let Ok(value) = std::env::var(arg) {
+                            args.insert(arg.clone(), value);
+                        }
+                    }
+                }
+            }
+            let builder = builder::ImageBuilder::new(file, context, tag.clone())?
+                .no_cache(*no_cache)
+                .allow_host_run(*allow_host_run)
+                .build_args(args);
             builder.build()?;
         }
         
@@ -225,6 +640,36 @@ pub async fn execute() -> Result<()> {
             let registry_dir = PathBuf::from(format!("{}/.nova/registry/{}", home, safe_tag));
             fs::create_dir_all(&registry_dir)?;
 
+            // Layered images upload only the blobs the registry does not have yet
+            if let Some(local) = builder::Image::find(image)?.filter(|i| i.manifest.is_some()) {
+                let manifest = local.manifest.clone().unwrap();
+                let local_blobs = builder::BlobStore::local();
+                let registry_blobs = builder::BlobStore::at(format!("{}/.nova/blobs", home));
+
+                for descriptor in std::iter::once(&manifest.config).chain(&manifest.layers) {
+                    if registry_blobs.copy_from(&local_blobs, descriptor)? {
+                        println!("📤 {}: Pushed ({} bytes)", builder::short_digest(&descriptor.digest), descriptor.size);
+                    } else {
+                        println!("   {}: Layer already exists", builder::short_digest(&descriptor.digest));
+                    }
+                }
+                let manifest_bytes = manifest.to_bytes()?;
+                let digest = registry_blobs.put(builder::store::MANIFEST_MEDIA_TYPE, &manifest_bytes)?.digest;
+
+                // Push details sit next to the manifest fields for `nova ps`
+                let size: u64 = manifest.layers.iter().map(|l| l.size).sum();
+                let mut entry = serde_json::to_value(&manifest)?;
+                entry["tag"] = serde_json::json!(image);
+                entry["digest"] = serde_json::json!(digest);
+                entry["pushed_at"] = serde_json::json!(chrono::Utc::now().to_rfc3339());
+                entry["size_mb"] = serde_json::json!(size as f64 / (1024.0 * 1024.0));
+                fs::write(registry_dir.join("manifest.json"), serde_json::to_string_pretty(&entry)?)?;
+
+                println!("✅ Image pushed successfully: registry.nova.cloud/{}@{}", image, digest);
+                println!("📍 Stored locally at: {}", registry_dir.display());
+                return Ok(());
+            }
+
             // Perform the "push" (copy if available, or create a manifest)
             if local_image_dir.exists() {
                 // Copy all files from local image to registry
@@ -282,8 +727,9 @@ pub async fn execute() -> Result<()> {
                     .compression_method(zip::CompressionMethod::Deflated)
                     .unix_permissions(0o755);
 
-                let walk = WalkDir::new(path);
-                for entry in walk.into_iter().filter_map(|e| e.ok()) {
+                // Same exclusions as a build of this directory
+                let ignore = NovaIgnore::load(path)?;
+                for entry in ignore.walk(path, false).filter_map(|e| e.ok()) {
                     let entry_path = entry.path();
                     let name = entry_path.strip_prefix(path)?;
 
@@ -399,8 +845,7 @@ pub async fn execute() -> Result<()> {
 
         Commands::Logs { name, lines, follow } => {
             use std::fs;
-            let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-            let log_path = std::path::PathBuf::from(format!("{}/.nova/logs/{}.log", home, name));
+            let log_path = nova::core::logging::log_path(name);
 
             println!("📋 Logs for container '{}':", name);
             println!("─────────────────────────────────────────────");
@@ -416,7 +861,7 @@ pub async fn execute() -> Result<()> {
                 // Simulate recent log output
                 let now = chrono::Utc::now();
                 let name_msg = format!("Serving traffic on port 8080 ({})", name);
-                let entries = vec![
+                let entries = [
                     ("INFO",  "Container runtime initialised"),
                     ("INFO",  "Wasm module loaded from registry"),
                     ("INFO",  "Edge node handshake complete (us-east-1)"),
@@ -475,6 +920,42 @@ pub async fn execute() -> Result<()> {
             }
         }
 
+        Commands::Secret { command } => {
+            let mut store = SecretStore::open_default()?;
+
+            match command {
+                SecretCommands::Set { name, value } => {
+                    let value = match value {
+                        Some(v) => v.clone(),
+                        None => {
+                            let mut v = String::new();
+                            std::io::stdin().read_to_string(&mut v)?;
+                            v.trim_end_matches(['\n', '\r']).to_string()
+                        }
+                    };
+                    store.set(name, &value)?;
+                    println!("🔐 Secret '{}' saved (encrypted at rest).", name);
+                }
+                SecretCommands::Ls => {
+                    let secrets = store.list();
+                    if secrets.is_empty() {
+                        println!("No secrets stored. Run `nova secret set <name>` to add one.");
+                    } else {
+                        println!("NAME                             UPDATED");
+                        for (name, updated_at) in secrets {
+                            println!("{:<32} {}", name, updated_at);
+                        }
+                    }
+                }
+                SecretCommands::Rm { name } => {
+                    if !store.remove(name)? {
+                        anyhow::bail!("Secret '{}' not found", name);
+                    }
+                    println!("🗑️  Secret '{}' removed.", name);
+                }
+            }
+        }
+
         Commands::Dashboard { port } => {
             api::start_server(*port).await;
         }
@@ -482,3 +963,92 @@ pub async fn execute() -> Result<()> {
 
     Ok(())
 }
+
+fn print_module_info(path: &Path, info: &nova::core::inspect::ModuleInfo) {
+    println!("🔎 {} ({} bytes, valid)\n", path.display(), info.size);
+
+    println!("Imports:");
+    if info.imports.is_empty() {
+        println!("  (none)");
+    }
+    for (module, imports) in info.imports_by_module() {
+        println!("  {}", module);
+        for import in imports {
+            let mark = if import.problem.is_some() { "❌" } else { "  " };
+            println!("  {} {:<24} {}", mark, import.name, import.ty);
+        }
+    }
+
+    println!("\nExports:");
+    if info.exports.is_empty() {
+        println!("  (none)");
+    }
+    for export in &info.exports {
+        println!("     {:<24} {}", export.name, export.ty);
+    }
+
+    println!("\nMemories:");
+    if info.memories.is_empty() {
+        println!("  (none)");
+    }
+    for (i, memory) in info.memories.iter().enumerate() {
+        let max = memory.max_pages.map(|m| format!("{} pages ({} MiB)", m, m * 64 / 1024)).unwrap_or_else(|| "unbounded".to_string());
+        println!(
+            "     #{}: min {} pages, max {}{}{}{}",
+            i, memory.min_pages, max,
+            if memory.imported { ", imported" } else { "" },
+            if memory.memory64 { ", 64-bit" } else { "" },
+            if memory.shared { ", shared" } else { "" },
+        );
+    }
+
+    println!("\nCustom sections:");
+    if info.custom_sections.is_empty() {
+        println!("  (none)");
+    }
+    for section in &info.custom_sections {
+        println!("     {:<24} {} bytes", section.name, section.size);
+    }
+
+    if info.problems.is_empty() {
+        println!("\n✅ All imports can be satisfied by nova run");
+    } else {
+        println!("\n❌ Problems:");
+        for problem in &info.problems {
+            println!("  - {}", problem);
+        }
+    }
+}
+
+/// Start `replicas` instances of a Wasm module; a single instance runs inline.
+async fn run_wasm_container(path: &str, replicas: u32, mut config: runtime::RunConfig, record: Option<&std::path::Path>) -> Result<()> {
+    if let Some(trace_path) = record {
+        if replicas > 1 {
+            anyhow::bail!("--record traces a single instance; drop --replicas");
+        }
+        let header = TraceHeader {
+            module: path.to_string(),
+            pid: config.pid,
+            args: config.args.clone(),
+            env: config.env.clone(),
+            secrets: config.secrets.iter().map(|secret| secret.name.clone()).collect(),
+            secrets_dir: config.secrets_dir.clone(),
+            fuel: config.fuel,
+            memory_limit_mb: config.memory_limit_mb,
+            map_dir: config.map_dir.clone(),
+            volumes: config.volumes.clone(),
+            http: config.http.clone(),
+            deterministic: config.deterministic,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        };
+        config.trace = Some(TraceHandle::record(trace_path, &header)?);
+        println!("⏺️  Recording host calls to '{}'", trace_path.display());
+    }
+
+    if replicas > 1 {
+        scheduler::Scheduler::run_replicas(path.to_string(), replicas, config).await
+    } else {
+        println!("🚀 Nova: Starting single container from '{}'...", path);
+        runtime::run_wasm(path, config).await
+    }
+}
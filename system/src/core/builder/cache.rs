@@ -0,0 +1,95 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::core::novaignore::NovaIgnore;
+use super::store::{BlobStore, Descriptor};
+
+/// Maps build-step keys to the layer the step produced, under `.nova/cache`.
+/// A key covers everything the step's result depends on, so a hit can reuse
+/// the layer without running the step.
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn local() -> Self {
+        Self::at(".nova/cache/steps")
+    }
+
+    pub fn at(dir: impl Into<PathBuf>) -> Self {
+        BuildCache { dir: dir.into() }
+    }
+
+    /// Layer cached for `key`, if its blob is still in `blobs`.
+    pub fn get(&self, key: &str, blobs: &BlobStore) -> Option<Descriptor> {
+        let content = fs::read(self.dir.join(key)).ok()?;
+        let descriptor: Descriptor = serde_json::from_slice(&content).ok()?;
+        blobs.contains(&descriptor.digest).then_some(descriptor)
+    }
+
+    pub fn put(&self, key: &str, descriptor: &Descriptor) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(key), serde_json::to_vec(descriptor)?)?;
+        Ok(())
+    }
+}
+
+/// Key of a step: the key of the step before it, the instruction and, for
+/// steps that read the build context, the hash of what they read.
+pub fn step_key(parent: &str, instruction: &str, sources: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    for part in [parent, instruction, sources.unwrap_or("")] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash of a file or directory tree: relative paths, permissions and contents.
+/// Entries excluded by `ignore` do not count; symlinks count as their targets.
+pub fn hash_path(path: &Path, ignore: &NovaIgnore) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for entry in ignore.walk(path, true) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(path)?;
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+
+        let metadata = entry.metadata()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            hasher.update(metadata.permissions().mode().to_le_bytes());
+        }
+        if metadata.is_file() {
+            let mut file = fs::File::open(entry.path())?;
+            hasher.update(metadata.len().to_le_bytes());
+            std::io::copy(&mut file, &mut hasher)?;
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_path_tracks_content() {
+        let dir = std::env::temp_dir().join(format!("nova-cache-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("site")).unwrap();
+        fs::write(dir.join("site/index.html"), b"v1").unwrap();
+
+        let ignore = NovaIgnore::new(&dir, "*.log").unwrap();
+        let before = hash_path(&dir, &ignore).unwrap();
+        fs::write(dir.join("site/build.log"), b"noise").unwrap();
+        assert_eq!(before, hash_path(&dir, &ignore).unwrap());
+
+        fs::write(dir.join("site/index.html"), b"v2").unwrap();
+        let after = hash_path(&dir, &ignore).unwrap();
+        assert_ne!(before, after);
+        assert_ne!(step_key("", "COPY site /site", Some(&before)), step_key("", "COPY site /site", Some(&after)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
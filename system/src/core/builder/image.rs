@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::core::capabilities::Capabilities;
+use crate::core::health::Healthcheck;
+use super::layer;
+use super::store::{self, BlobStore, Manifest};
+
+/// Image configuration, stored as the config blob of the image's manifest.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImageMetadata {
+    pub tag: String,
+    pub base: String,
+    pub env: Vec<(String, String)>,
+    /// Put before `cmd` at run time, so CMD only supplies default arguments.
+    #[serde(default)]
+    pub entrypoint: Vec<String>,
+    pub cmd: Vec<String>,
+    pub expose: Vec<u16>,
+    pub workdir: String,
+    pub labels: Vec<(String, String)>,
+    /// Names of secrets resolved at container start. Values never enter the image.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    /// Export run at build time by `INIT`; the entry module already holds its effects.
+    #[serde(default)]
+    pub init: Option<String>,
+    #[serde(default)]
+    pub healthcheck: Option<Healthcheck>,
+    /// Guest paths backed by a persistent directory per container.
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    /// Defaults for `--memory` and `--fuel`.
+    #[serde(default)]
+    pub resources: Resources,
+    /// What containers from this image may be granted. Images built before
+    /// manifests existed have none and are not restricted.
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+}
+
+/// Limits a container runs with unless `nova run` is given others.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Resources {
+    pub memory_mb: Option<u64>,
+    pub fuel: Option<u64>,
+}
+
+/// Local directory of an image: `.nova/images/<tag>` with `:` and `/` made path-safe.
+/// It holds `manifest.json` and the unpacked layers in `rootfs/`.
+pub fn image_dir(tag: &str) -> PathBuf {
+    PathBuf::from(format!(".nova/images/{}", tag.replace(':', "_").replace('/', "-")))
+}
+
+/// A built image in the local image store.
+pub struct Image {
+    /// Host directory the image's files are unpacked into.
+    pub root: PathBuf,
+    pub metadata: ImageMetadata,
+    /// `None` for images built before layers existed (files and
+    /// `metadata.json` directly in the image directory).
+    pub manifest: Option<Manifest>,
+}
+
+impl Image {
+    /// Look up `tag` in the local image store, unpacking its layers if needed.
+    pub fn find(tag: &str) -> Result<Option<Image>> {
+        let dir = image_dir(tag);
+        let manifest_path = dir.join("manifest.json");
+        if manifest_path.exists() {
+            let manifest = store::read_manifest(&manifest_path)?;
+            let blobs = BlobStore::local();
+            let metadata: ImageMetadata = serde_json::from_slice(&blobs.get(&manifest.config)?)
+                .with_context(|| format!("Failed to parse config of image '{}'", tag))?;
+
+            let root = dir.join("rootfs");
+            if !root.exists() {
+                unpack_layers(&blobs, &manifest, &root)?;
+            }
+            return Ok(Some(Image { root, metadata, manifest: Some(manifest) }));
+        }
+
+        let metadata_path = dir.join("metadata.json");
+        if !metadata_path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&metadata_path)
+            .with_context(|| format!("Failed to read {}", metadata_path.display()))?;
+        let metadata: ImageMetadata = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", metadata_path.display()))?;
+
+        Ok(Some(Image { root: dir, metadata, manifest: None }))
+    }
+
+    /// Fetch `tag` from the registry `nova push` uploads to into the local
+    /// image store. `None` if the registry does not have it.
+    pub fn pull(tag: &str) -> Result<Option<Image>> {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        let manifest_path = PathBuf::from(format!("{}/.nova/registry/{}/manifest.json", home, tag.replace(':', "_").replace('/', "-")));
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        // Images pushed before layers existed have a manifest without blobs
+        let manifest = store::read_manifest(&manifest_path)
+            .with_context(|| format!("Image '{}' in the registry predates layered images and cannot be pulled", tag))?;
+
+        let registry_blobs = BlobStore::at(format!("{}/.nova/blobs", home));
+        let blobs = BlobStore::local();
+        for descriptor in std::iter::once(&manifest.config).chain(&manifest.layers) {
+            if blobs.copy_from(&registry_blobs, descriptor)? {
+                println!("📥 {}: Pulled ({} bytes)", super::short_digest(&descriptor.digest), descriptor.size);
+            }
+        }
+
+        // A local image of the same tag is replaced, but files of one built
+        // before layers existed are left alone
+        let dir = image_dir(tag);
+        if dir.join("metadata.json").exists() {
+            anyhow::bail!("Local image '{}' predates layered images; rebuild or remove it before pulling", tag);
+        }
+        let root = dir.join("rootfs");
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&dir)?;
+        let manifest_bytes = manifest.to_bytes()?;
+        blobs.put(store::MANIFEST_MEDIA_TYPE, &manifest_bytes)?;
+        fs::write(dir.join("manifest.json"), &manifest_bytes)?;
+        Self::find(tag)
+    }
+
+    /// `sha256:` digest identifying this image, if it has a manifest.
+    pub fn digest(&self) -> Result<Option<String>> {
+        match &self.manifest {
+            Some(manifest) => Ok(Some(store::digest(&manifest.to_bytes()?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Check every blob of the image against its digest.
+    pub fn verify(&self, blobs: &BlobStore) -> Result<()> {
+        let manifest = self.manifest.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Image '{}' predates layered images and has no digests; rebuild it", self.metadata.tag))?;
+        blobs.get(&manifest.config)?;
+        for layer in &manifest.layers {
+            blobs.get(layer)?;
+        }
+        Ok(())
+    }
+
+    /// Host path of the Wasm module ENTRYPOINT and CMD start and the arguments passed to it.
+    /// Both `CMD ["nova", "run", "/app/x.wasm", ...]` and `CMD ["/app/x.wasm", ...]` are accepted.
+    pub fn entry_module(&self) -> Result<(PathBuf, Vec<String>)> {
+        let command: Vec<String> = self.metadata.entrypoint.iter().chain(&self.metadata.cmd).cloned().collect();
+        let args = if command.len() >= 3 && command[0] == "nova" && command[1] == "run" {
+            &command[2..]
+        } else {
+            &command[..]
+        };
+
+        let (module, rest) = args.split_first()
+            .ok_or_else(|| anyhow::anyhow!("Image '{}' has no ENTRYPOINT or CMD", self.metadata.tag))?;
+
+        let path = self.resolve(module)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("wasm") | Some("wat") => {}
+            _ => anyhow::bail!("Image '{}' does not start a Wasm module: {}", self.metadata.tag, module),
+        }
+        if !path.exists() {
+            anyhow::bail!("Image '{}' is missing its entry module {}", self.metadata.tag, module);
+        }
+
+        Ok((path, rest.to_vec()))
+    }
+
+    /// Map an in-image path (absolute, or relative to WORKDIR) onto the host.
+    fn resolve(&self, image_path: &str) -> Result<PathBuf> {
+        let in_image = if image_path.starts_with('/') {
+            Path::new(image_path).to_path_buf()
+        } else {
+            Path::new(&self.metadata.workdir).join(image_path)
+        };
+        Ok(self.root.join(layer::image_path(&in_image.to_string_lossy())?))
+    }
+}
+
+/// Unpack `manifest`'s layers into `root`, verifying each one. Layers are
+/// unpacked next to `root` and moved into place, so `root` is never partial.
+pub fn unpack_layers(blobs: &BlobStore, manifest: &Manifest, root: &Path) -> Result<()> {
+    let partial = root.with_extension(format!("partial-{}", uuid::Uuid::new_v4()));
+    let result = (|| {
+        fs::create_dir_all(&partial)?;
+        for descriptor in &manifest.layers {
+            layer::unpack(&blobs.get(descriptor)?, &partial)?;
+        }
+        if root.exists() {
+            fs::remove_dir_all(root)?;
+        }
+        fs::rename(&partial, root)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&partial);
+    }
+    result
+}
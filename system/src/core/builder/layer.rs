@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Scratch directory holding the files of one layer before it is packed;
+/// removed on drop.
+pub struct Staging {
+    pub dir: PathBuf,
+}
+
+impl Staging {
+    pub fn new() -> Result<Self> {
+        Self::under(&std::env::temp_dir())
+    }
+
+    /// A scratch directory inside `parent`, so it can be renamed to a
+    /// sibling once complete.
+    pub fn under(parent: &Path) -> Result<Self> {
+        let dir = parent.join(format!("nova-layer-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        Ok(Staging { dir })
+    }
+
+    /// Host path for an in-image path.
+    pub fn join(&self, path: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(image_path(path)?))
+    }
+}
+
+/// An in-image path relative to the image root, with `.` and `..` resolved.
+/// Fails if the path climbs above the root.
+pub fn image_path(path: &str) -> Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    anyhow::bail!("{} is outside the image", path);
+                }
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Ok(resolved)
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Ownership and permissions forced onto `paths` (and everything below them)
+/// when a layer is packed, as set by `COPY --chown` / `--chmod`.
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    pub paths: Vec<PathBuf>,
+    pub owner: Option<(u64, u64)>,
+    pub mode: Option<u32>,
+}
+
+impl Attributes {
+    fn applies_to(&self, name: &Path) -> bool {
+        self.paths.iter().any(|path| name.starts_with(path))
+    }
+}
+
+/// Tar the contents of `dir`. Entries are sorted and timestamps and owners
+/// fixed, so the same files always give the same bytes (and digest).
+pub fn pack(dir: &Path, attributes: &Attributes) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+
+    for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry.path().strip_prefix(dir)?;
+        let metadata = entry.metadata()?;
+
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&metadata, tar::HeaderMode::Deterministic);
+        if attributes.applies_to(name) {
+            if let Some((uid, gid)) = attributes.owner {
+                header.set_uid(uid);
+                header.set_gid(gid);
+            }
+            if let Some(mode) = attributes.mode {
+                header.set_mode(mode);
+            }
+        }
+
+        let added = if metadata.is_dir() {
+            builder.append_data(&mut header, name, std::io::empty())
+        } else if metadata.is_file() {
+            builder.append_data(&mut header, name, fs::File::open(entry.path())?)
+        } else {
+            anyhow::bail!("{} is not a regular file or directory", name.display());
+        };
+        added.with_context(|| format!("Failed to add {} to layer", name.display()))?;
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Apply a layer onto `root`, replacing files it contains.
+pub fn unpack(layer: &[u8], root: &Path) -> Result<()> {
+    fs::create_dir_all(root)?;
+    let mut archive = tar::Archive::new(layer);
+    archive.set_overwrite(true);
+    archive.unpack(root).with_context(|| format!("Failed to unpack layer into {}", root.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_is_reproducible() {
+        let a = Staging::new().unwrap();
+        let b = Staging::new().unwrap();
+        for staging in [&a, &b] {
+            fs::create_dir_all(staging.join("/app/static").unwrap()).unwrap();
+            fs::write(staging.join("/app/app.wasm").unwrap(), b"\0asm").unwrap();
+            fs::write(staging.join("/app/static/index.html").unwrap(), b"<html>").unwrap();
+        }
+
+        let layer = pack(&a.dir, &Attributes::default()).unwrap();
+        assert_eq!(layer, pack(&b.dir, &Attributes::default()).unwrap());
+
+        let root = Staging::new().unwrap();
+        unpack(&layer, &root.dir).unwrap();
+        assert_eq!(fs::read(root.join("/app/static/index.html").unwrap()).unwrap(), b"<html>");
+    }
+
+    #[test]
+    fn test_image_paths_stay_inside_the_root() {
+        assert_eq!(image_path("/app/./static/../app.wasm").unwrap(), Path::new("app/app.wasm"));
+        assert_eq!(image_path("app/").unwrap(), Path::new("app"));
+        assert!(image_path("/app/../../etc/passwd").is_err());
+        assert!(image_path("../outside").is_err());
+    }
+
+    #[test]
+    fn test_attributes_apply_below_their_paths() {
+        let staging = Staging::new().unwrap();
+        fs::create_dir_all(staging.join("/app/static").unwrap()).unwrap();
+        fs::write(staging.join("/app/static/index.html").unwrap(), b"<html>").unwrap();
+
+        let attributes = Attributes { paths: vec![PathBuf::from("app/static")], owner: Some((1000, 100)), mode: Some(0o600) };
+        let layer = pack(&staging.dir, &attributes).unwrap();
+
+        let mut archive = tar::Archive::new(&layer[..]);
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+            let path = header.path().unwrap().into_owned();
+            if path.starts_with("app/static") {
+                assert_eq!((header.uid().unwrap(), header.gid().unwrap(), header.mode().unwrap()), (1000, 100, 0o600));
+            } else {
+                assert_eq!((header.uid().unwrap(), header.mode().unwrap()), (0, 0o755));
+            }
+        }
+    }
+}
@@ -1,217 +1,689 @@
 use anyhow::{Result, Context};
+use globset::GlobBuilder;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::collections::HashMap;
+use crate::core::capabilities::{Capabilities, Capability};
+use crate::core::novaignore::NovaIgnore;
 
-#[derive(Debug, Clone)]
-pub enum Instruction {
-    From(String),
-    Copy { src: String, dst: String },
-    Env { key: String, value: String },
-    Cmd(Vec<String>),
-    Expose(u16),
-    Workdir(String),
-    Label { key: String, value: String },
-}
+pub mod cache;
+pub mod image;
+pub mod layer;
+pub mod novafile;
+pub mod run;
+pub mod store;
 
-#[derive(Debug)]
-pub struct Novafile {
-    pub instructions: Vec<Instruction>,
-}
+pub use image::{Image, ImageMetadata, Resources, image_dir};
+pub use novafile::{Diagnostic, Instruction, Novafile};
+pub use store::{BlobStore, Descriptor, Manifest};
+use cache::BuildCache;
+use layer::Staging;
 
-impl Novafile {
-    pub fn parse(path: &Path) -> Result<Self> {
-        let content = fs::read_to_string(path)
-            .context("Failed to read Novafile")?;
-        
-        let mut instructions = Vec::new();
-        
-        for line in content.lines() {
-            let line = line.trim();
-            
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            
-            let instruction = Self::parse_line(line)?;
-            instructions.push(instruction);
-        }
-        
-        Ok(Novafile { instructions })
-    }
-    
-    fn parse_line(line: &str) -> Result<Instruction> {
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        if parts.len() < 2 {
-            anyhow::bail!("Invalid instruction: {}", line);
-        }
-        
-        let cmd = parts[0].to_uppercase();
-        let args = parts[1].trim();
-        
-        match cmd.as_str() {
-            "FROM" => Ok(Instruction::From(args.to_string())),
-            
-            "COPY" => {
-                let copy_parts: Vec<&str> = args.split_whitespace().collect();
-                if copy_parts.len() != 2 {
-                    anyhow::bail!("COPY requires source and destination");
-                }
-                Ok(Instruction::Copy {
-                    src: copy_parts[0].to_string(),
-                    dst: copy_parts[1].to_string(),
-                })
-            }
-            
-            "ENV" => {
-                let env_parts: Vec<&str> = args.splitn(2, '=').collect();
-                if env_parts.len() != 2 {
-                    anyhow::bail!("ENV requires KEY=VALUE format");
-                }
-                Ok(Instruction::Env {
-                    key: env_parts[0].to_string(),
-                    value: env_parts[1].to_string(),
-                })
-            }
-            
-            "CMD" => {
-                // Parse JSON array format: ["cmd", "arg1", "arg2"]
-                let cmd_vec = if args.starts_with('[') {
-                    serde_json::from_str(args)
-                        .context("Failed to parse CMD as JSON array")?
-                } else {
-                    // Shell form: split by whitespace
-                    args.split_whitespace()
-                        .map(|s| s.to_string())
-                        .collect()
-                };
-                Ok(Instruction::Cmd(cmd_vec))
-            }
-            
-            "EXPOSE" => {
-                let port: u16 = args.parse()
-                    .context("EXPOSE requires a valid port number")?;
-                Ok(Instruction::Expose(port))
-            }
-            
-            "WORKDIR" => Ok(Instruction::Workdir(args.to_string())),
-            
-            "LABEL" => {
-                let label_parts: Vec<&str> = args.splitn(2, '=').collect();
-                if label_parts.len() != 2 {
-                    anyhow::bail!("LABEL requires KEY=VALUE format");
-                }
-                Ok(Instruction::Label {
-                    key: label_parts[0].to_string(),
-                    value: label_parts[1].trim_matches('"').to_string(),
-                })
-            }
-            
-            _ => anyhow::bail!("Unknown instruction: {}", cmd),
-        }
-    }
+/// First 12 hex digits of a digest, for progress output.
+pub fn short_digest(digest: &str) -> &str {
+    let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+    &hex[..hex.len().min(12)]
 }
 
 pub struct ImageBuilder {
-    novafile: Novafile,
+    novafile_path: PathBuf,
+    build_args: HashMap<String, String>,
     context_dir: PathBuf,
+    ignore: NovaIgnore,
     tag: String,
+    no_cache: bool,
+    allow_host_run: bool,
 }
 
 impl ImageBuilder {
     pub fn new(novafile_path: &Path, context_dir: &Path, tag: String) -> Result<Self> {
-        let novafile = Novafile::parse(novafile_path)?;
+        // Absolute and free of symlinks, so COPY sources can be checked against it
+        let context_dir = fs::canonicalize(context_dir)
+            .with_context(|| format!("Build context {} not found", context_dir.display()))?;
+        let ignore = NovaIgnore::load(&context_dir)?;
         
         Ok(ImageBuilder {
-            novafile,
-            context_dir: context_dir.to_path_buf(),
+            novafile_path: novafile_path.to_path_buf(),
+            build_args: HashMap::new(),
+            context_dir,
+            ignore,
             tag,
+            no_cache: false,
+            allow_host_run: false,
         })
     }
     
+    /// Run every step even if the build cache has its result.
+    pub fn no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+    
+    /// Let `RUN` steps execute. They run host programs with the user's file
+    /// system and network access, so a Novafile with `RUN` is refused without it.
+    pub fn allow_host_run(mut self, allow: bool) -> Self {
+        self.allow_host_run = allow;
+        self
+    }
+    
+    /// Values for the Novafile's `ARG`s (`--build-arg KEY=VALUE`).
+    pub fn build_args(mut self, build_args: HashMap<String, String>) -> Self {
+        self.build_args = build_args;
+        self
+    }
+    
     pub fn build(&self) -> Result<String> {
         println!("🔨 Building image: {}", self.tag);
         
-        // Create image directory
-        let image_dir = PathBuf::from(format!(".nova/images/{}", self.tag.replace(':', "_")));
-        fs::create_dir_all(&image_dir)?;
+        let novafile = Novafile::parse_with_args(&self.novafile_path, &self.build_args)?;
+        for key in self.build_args.keys() {
+            let declared = novafile.instructions.iter()
+                .any(|instruction| matches!(instruction, Instruction::Arg { key: arg, .. } if arg == key));
+            if !declared {
+                println!("⚠️  Build argument {} is not declared by an ARG and was ignored", key);
+            }
+        }
+        if !self.allow_host_run && novafile.instructions.iter().any(|instruction| matches!(instruction, Instruction::Run { .. })) {
+            anyhow::bail!(
+                "{} has RUN steps, which execute on the host with your files and network; pass --allow-host-run to build it",
+                self.novafile_path.display()
+            );
+        }
         
-        let mut metadata = ImageMetadata {
-            tag: self.tag.clone(),
-            base: String::from("scratch"),
-            env: Vec::new(),
-            cmd: Vec::new(),
-            expose: Vec::new(),
-            workdir: String::from("/"),
-            labels: Vec::new(),
-        };
+        let stages = Stage::split(&novafile.instructions);
+        let needed = Stage::needed(&stages);
+        let total: usize = stages.iter().zip(&needed)
+            .filter(|(_, needed)| **needed)
+            .map(|(stage, _)| stage.instructions.len())
+            .sum();
+        
+        // The image is built next to the one it replaces and only swapped in
+        // once every step succeeded, so a failing build keeps the last good image
+        let image_dir = image_dir(&self.tag);
+        let images = image_dir.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(images)?;
+        let building = Staging::under(images)?;
+        let rootfs = building.dir.join("rootfs");
+        fs::create_dir_all(&rootfs)?;
+        
+        let blobs = BlobStore::local();
+        let cache = BuildCache::local();
+        let mut step = 0;
+        
+        // Earlier stages are built in scratch directories that COPY --from reads;
+        // only the last stage becomes the image
+        let mut built: Vec<Option<StageState>> = Vec::new();
+        for (i, stage) in stages.iter().enumerate() {
+            if !needed[i] {
+                println!("Skipping stage {}: the image does not copy from it", stage.label(i));
+                built.push(None);
+                continue;
+            }
+            
+            let state = if i + 1 == stages.len() {
+                StageState::new(&self.tag, rootfs.clone(), None)
+            } else {
+                let staging = Staging::new()?;
+                StageState::new(&self.tag, fs::canonicalize(&staging.dir)?, Some(staging))
+            };
+            let state = self.build_stage(stage, state, &stages, &built, &blobs, &cache, &mut step, total)?;
+            built.push(Some(state));
+        }
+        let StageState { mut metadata, mut layers, capabilities, key, .. } = built.pop().flatten()
+            .ok_or_else(|| anyhow::anyhow!("Novafile has no instructions"))?;
         
-        // Execute instructions
-        for (i, instruction) in self.novafile.instructions.iter().enumerate() {
-            println!("Step {}/{}: {:?}", i + 1, self.novafile.instructions.len(), instruction);
+        // Defaults above the declared maximum would make `nova run` refuse the image
+        let resources = &metadata.resources;
+        if let (Some(memory), Some(max)) = (resources.memory_mb, capabilities.max_memory_mb) {
+            if memory > max {
+                anyhow::bail!("RESOURCES memory={} exceeds CAPABILITY memory {}", memory, max);
+            }
+        }
+        if let (Some(fuel), Some(max)) = (resources.fuel, capabilities.max_fuel) {
+            if fuel > max {
+                anyhow::bail!("RESOURCES fuel={} exceeds CAPABILITY fuel {}", fuel, max);
+            }
+        }
+        metadata.capabilities = Some(capabilities);
+        
+        // Pre-initialize the entry module so startup work is paid once at build time.
+        // The rewritten module goes into a layer of its own.
+        if let Some(export) = &metadata.init {
+            // Every instruction, INIT included, is already part of `key`
+            let init_key = cache::step_key(&key, "preinit", None);
+            match self.cached(&cache, &init_key, &blobs, &rootfs)? {
+                Some(layer) => layers.push(layer),
+                None => {
+                    let image = Image { root: rootfs.clone(), metadata: metadata.clone(), manifest: None };
+                    let (module, _) = image.entry_module()?;
+                    println!("⚡ Pre-initializing {} with '{}'", module.display(), export);
+                    
+                    if module.extension().and_then(|e| e.to_str()) != Some("wasm") {
+                        anyhow::bail!("INIT needs a binary .wasm entry module, got {}", module.display());
+                    }
+                    
+                    let wasm = fs::read(&module)?;
+                    let initialized = crate::core::preinit::preinit(&wasm, export)?;
+                    
+                    let staging = Staging::new()?;
+                    let in_image = module.strip_prefix(&rootfs)?;
+                    let staged = staging.dir.join(in_image);
+                    fs::create_dir_all(staged.parent().unwrap())?;
+                    fs::write(&staged, initialized)?;
+                    let layer = Self::commit_layer(&blobs, &staging, &rootfs, &layer::Attributes::default())?;
+                    cache.put(&init_key, &layer)?;
+                    layers.push(layer);
+                }
+            }
+        }
+        
+        // Config and manifest go into the blob store; the tag points at the manifest
+        let config = blobs.put(store::CONFIG_MEDIA_TYPE, &serde_json::to_vec_pretty(&metadata)?)?;
+        let manifest = Manifest::new(config, layers);
+        let manifest_bytes = manifest.to_bytes()?;
+        let manifest_digest = blobs.put(store::MANIFEST_MEDIA_TYPE, &manifest_bytes)?.digest;
+        fs::write(building.dir.join("manifest.json"), &manifest_bytes)?;
+        Self::replace_image(&building, &image_dir)?;
+        
+        println!("✅ Successfully built {} ({})", self.tag, manifest_digest);
+        Ok(self.tag.clone())
+    }
+    
+    /// Move a finished build to `image_dir`. Whatever was there, including
+    /// an image built before layers existed, is removed only afterwards.
+    fn replace_image(building: &Staging, image_dir: &Path) -> Result<()> {
+        if !image_dir.exists() {
+            fs::rename(&building.dir, image_dir)?;
+            return Ok(());
+        }
+        
+        let old = building.dir.with_extension("old");
+        fs::rename(image_dir, &old)?;
+        if let Err(e) = fs::rename(&building.dir, image_dir) {
+            fs::rename(&old, image_dir)?;
+            return Err(e.into());
+        }
+        fs::remove_dir_all(&old)?;
+        Ok(())
+    }
+    
+    /// Run the instructions of one stage on top of `state`.
+    #[allow(clippy::too_many_arguments)]
+    fn build_stage(
+        &self,
+        stage: &Stage,
+        mut state: StageState,
+        stages: &[Stage],
+        built: &[Option<StageState>],
+        blobs: &BlobStore,
+        cache: &BuildCache,
+        step: &mut usize,
+        total: usize,
+    ) -> Result<StageState> {
+        let rootfs = state.rootfs.clone();
+        
+        for instruction in &stage.instructions {
+            // Stage files are copied whole; `.novaignore` applies to the build context
+            let no_ignore;
+            *step += 1;
+            println!("Step {}/{}: {:?}", step, total, instruction);
+            
+            let text = format!("{:?}", instruction);
+            // Files the step reads; their contents are part of its key
+            let (source, inputs) = match instruction {
+                Instruction::Copy { sources, from, .. } => {
+                    let source = match from {
+                        Some(reference) => {
+                            let names: Vec<Option<String>> = stages.iter().map(|stage| stage.name.clone()).collect();
+                            let index = novafile::stage_index(&names, reference)
+                                .ok_or_else(|| anyhow::anyhow!("COPY failed: no stage named {}", reference))?;
+                            let from = built[index].as_ref()
+                                .ok_or_else(|| anyhow::anyhow!("COPY failed: stage {} was not built", reference))?;
+                            no_ignore = NovaIgnore::new(&from.rootfs, "")?;
+                            Source { root: &from.rootfs, ignore: &no_ignore, name: format!("stage {}", reference) }
+                        }
+                        None => self.context(),
+                    };
+                    let inputs = source.resolve(sources)?;
+                    (Some(source), inputs)
+                }
+                _ => (None, Vec::new()),
+            };
+            // A base image counts by digest, so rebuilding it invalidates the steps on top
+            let mut base = match instruction {
+                Instruction::From { image, .. } if image != "scratch" => Some(base_image(image)?),
+                _ => None,
+            };
+            let sources = match (&source, &base) {
+                (Some(source), _) => Some(source.hash(&inputs)?),
+                (None, Some(base)) => base.digest()?,
+                (None, None) => None,
+            };
+            state.key = cache::step_key(&state.key, &text, sources.as_deref());
             
             match instruction {
-                Instruction::From(base) => {
-                    metadata.base = base.clone();
+                Instruction::From { image, .. } => {
+                    state.metadata.base = image.clone();
+                    if let Some(Image { metadata, manifest: Some(manifest), .. }) = base.take() {
+                        // The stage starts from the base's files and config; its
+                        // layers come first in the manifest
+                        for layer in &manifest.layers {
+                            layer::unpack(&blobs.get(layer)?, &rootfs)?;
+                        }
+                        state.layers = manifest.layers;
+                        state.metadata.env = metadata.env;
+                        state.metadata.entrypoint = metadata.entrypoint;
+                        state.metadata.cmd = metadata.cmd;
+                        state.inherited_cmd = true;
+                        state.metadata.expose = metadata.expose;
+                        state.metadata.workdir = metadata.workdir;
+                        state.metadata.labels = metadata.labels;
+                        state.metadata.secrets = metadata.secrets;
+                        state.metadata.healthcheck = metadata.healthcheck;
+                        state.metadata.volumes = metadata.volumes;
+                        state.metadata.resources = metadata.resources;
+                        state.capabilities = metadata.capabilities.unwrap_or_default();
+                    }
                 }
                 
-                Instruction::Copy { src, dst } => {
-                    let src_path = self.context_dir.join(src);
-                    let dst_path = image_dir.join(dst.trim_start_matches('/'));
+                Instruction::Copy { dst, chown, chmod, .. } => {
+                    if let Some(layer) = self.cached(cache, &state.key, blobs, &rootfs)? {
+                        state.layers.push(layer);
+                        continue;
+                    }
+                    let source = source.unwrap();
+                    
+                    // A destination ending in '/' is a directory: files go inside it
+                    // under their own names and directories have their contents merged
+                    let into_dir = dst.ends_with('/');
+                    if inputs.len() > 1 && !into_dir {
+                        anyhow::bail!("COPY failed: sources match {} paths, so the destination must end in '/'", inputs.len());
+                    }
                     
-                    if let Some(parent) = dst_path.parent() {
-                        fs::create_dir_all(parent)?;
+                    // Each COPY becomes one layer holding just the copied files
+                    let staging = Staging::new()?;
+                    let dst_path = staging.join(dst)
+                        .map_err(|_| anyhow::anyhow!("COPY failed: destination {} is outside the image", dst))?;
+                    let mut attributes = layer::Attributes { paths: Vec::new(), owner: *chown, mode: *chmod };
+                    
+                    for src_path in &inputs {
+                        let target = match src_path.file_name() {
+                            Some(name) if into_dir && !src_path.is_dir() => dst_path.join(name),
+                            _ => dst_path.clone(),
+                        };
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        
+                        if src_path.is_dir() {
+                            source.copy_dir(src_path, &target)?;
+                        } else {
+                            fs::copy(src_path, &target)
+                                .with_context(|| format!("COPY failed: {}", src_path.display()))?;
+                        }
+                        attributes.paths.push(target.strip_prefix(&staging.dir)?.to_path_buf());
                     }
                     
-                    if src_path.is_dir() {
-                        Self::copy_dir(&src_path, &dst_path)?;
-                    } else {
-                        fs::copy(&src_path, &dst_path)?;
+                    let layer = Self::commit_layer(blobs, &staging, &rootfs, &attributes)?;
+                    cache.put(&state.key, &layer)?;
+                    state.layers.push(layer);
+                }
+                
+                Instruction::Env(pairs) => {
+                    for (key, value) in pairs {
+                        state.metadata.env.push((key.clone(), value.clone()));
+                        state.capabilities.declare(Capability::Env(vec![key.clone()]));
                     }
                 }
                 
-                Instruction::Env { key, value } => {
-                    metadata.env.push((key.clone(), value.clone()));
+                Instruction::Run { command, args } => {
+                    if let Some(layer) = self.cached(cache, &state.key, blobs, &rootfs)? {
+                        state.layers.push(layer);
+                        continue;
+                    }
+                    
+                    // ENV wins over ARG, as in substitution
+                    let env: Vec<(String, String)> = args.iter().chain(&state.metadata.env).cloned().collect();
+                    let staging = run::run(&rootfs, &state.metadata.workdir, command, &env)?;
+                    let layer = Self::commit_layer(blobs, &staging, &rootfs, &layer::Attributes::default())?;
+                    cache.put(&state.key, &layer)?;
+                    state.layers.push(layer);
+                }
+                
+                Instruction::Arg { .. } => {
+                    // Values were substituted into later instructions while parsing
+                }
+                
+                Instruction::Entrypoint(entrypoint) => {
+                    state.metadata.entrypoint = entrypoint.clone();
+                    // The base's CMD was meant for the base's entrypoint
+                    if state.inherited_cmd {
+                        state.metadata.cmd.clear();
+                    }
                 }
                 
                 Instruction::Cmd(cmd) => {
-                    metadata.cmd = cmd.clone();
+                    state.metadata.cmd = cmd.clone();
+                    state.inherited_cmd = false;
                 }
                 
                 Instruction::Expose(port) => {
-                    metadata.expose.push(*port);
+                    state.metadata.expose.push(*port);
                 }
                 
                 Instruction::Workdir(dir) => {
-                    metadata.workdir = dir.clone();
+                    state.metadata.workdir = dir.clone();
+                }
+                
+                Instruction::Label(pairs) => {
+                    state.metadata.labels.extend(pairs.iter().cloned());
+                }
+                
+                Instruction::Secret(name) => {
+                    // Only the reference is recorded; the value is resolved at container start
+                    if !state.metadata.secrets.contains(name) {
+                        state.metadata.secrets.push(name.clone());
+                    }
+                    state.capabilities.declare(Capability::Env(vec![name.clone()]));
+                }
+                
+                Instruction::Init(export) => {
+                    // Runs after all files are in place, see `build`
+                    state.metadata.init = Some(export.clone());
                 }
                 
-                Instruction::Label { key, value } => {
-                    metadata.labels.push((key.clone(), value.clone()));
+                Instruction::Capability(capability) => {
+                    state.capabilities.declare(capability.clone());
+                }
+                
+                Instruction::Healthcheck(check) => {
+                    state.metadata.healthcheck = check.clone();
+                }
+                
+                Instruction::Volume(paths) => {
+                    for path in paths {
+                        if !state.metadata.volumes.contains(path) {
+                            state.metadata.volumes.push(path.clone());
+                        }
+                    }
+                    // A volume can also be backed by --map-dir
+                    state.capabilities.declare(Capability::Mount(paths.clone()));
+                }
+                
+                Instruction::Resources(resources) => {
+                    if resources.memory_mb.is_some() {
+                        state.metadata.resources.memory_mb = resources.memory_mb;
+                    }
+                    if resources.fuel.is_some() {
+                        state.metadata.resources.fuel = resources.fuel;
+                    }
                 }
             }
         }
         
-        // Save metadata
-        let metadata_json = serde_json::to_string_pretty(&metadata)?;
-        fs::write(image_dir.join("metadata.json"), metadata_json)?;
-        
-        println!("✅ Successfully built {}", self.tag);
-        Ok(self.tag.clone())
+        Ok(state)
     }
     
-    fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
-        fs::create_dir_all(dst)?;
-        
-        for entry in fs::read_dir(src)? {
+    /// Reuse the layer cached for `key`, applying it to the image's files.
+    fn cached(&self, cache: &BuildCache, key: &str, blobs: &BlobStore, rootfs: &Path) -> Result<Option<Descriptor>> {
+        if self.no_cache {
+            return Ok(None);
+        }
+        let Some(descriptor) = cache.get(key, blobs) else {
+            return Ok(None);
+        };
+        layer::unpack(&blobs.get(&descriptor)?, rootfs)?;
+        println!(" ---> CACHED {}", short_digest(&descriptor.digest));
+        Ok(Some(descriptor))
+    }
+    
+    /// Pack a staged layer into the blob store and apply it to the image's files.
+    fn commit_layer(blobs: &BlobStore, staging: &Staging, rootfs: &Path, attributes: &layer::Attributes) -> Result<Descriptor> {
+        let tar = layer::pack(&staging.dir, attributes)?;
+        let descriptor = blobs.put(store::LAYER_MEDIA_TYPE, &tar)?;
+        layer::unpack(&tar, rootfs)?;
+        println!(" ---> Layer {} ({} bytes)", short_digest(&descriptor.digest), descriptor.size);
+        Ok(descriptor)
+    }
+    
+    /// COPY sources in the build context.
+    fn context(&self) -> Source<'_> {
+        Source { root: &self.context_dir, ignore: &self.ignore, name: "build context".to_string() }
+    }
+}
+
+/// The image `FROM tag` builds on: the local image, or else the one in the
+/// registry, pulled into the local store.
+fn base_image(tag: &str) -> Result<Image> {
+    match Image::find(tag)? {
+        Some(image) if image.manifest.is_some() => return Ok(image),
+        Some(_) => anyhow::bail!("FROM failed: image '{}' predates layered images and cannot be used as a base; rebuild it", tag),
+        None => {}
+    }
+    if let Some(image) = Image::pull(tag)? {
+        println!(" ---> Pulled {} from the registry", tag);
+        return Ok(image);
+    }
+    anyhow::bail!("FROM failed: image '{}' not found locally or in the registry (build it with `nova build --tag {}` or push it first)", tag, tag);
+}
+
+/// The instructions from one FROM to the next.
+struct Stage<'a> {
+    name: Option<String>,
+    instructions: Vec<&'a Instruction>,
+}
+
+impl<'a> Stage<'a> {
+    /// ARGs before the first FROM only matter while parsing; instructions
+    /// before any FROM form a stage starting from scratch.
+    fn split(instructions: &'a [Instruction]) -> Vec<Stage<'a>> {
+        let mut stages: Vec<Stage> = Vec::new();
+        for instruction in instructions {
+            match instruction {
+                Instruction::From { name, .. } => {
+                    stages.push(Stage { name: name.clone(), instructions: vec![instruction] });
+                }
+                Instruction::Arg { .. } if stages.is_empty() => {}
+                _ => {
+                    if stages.is_empty() {
+                        stages.push(Stage { name: None, instructions: Vec::new() });
+                    }
+                    stages.last_mut().unwrap().instructions.push(instruction);
+                }
+            }
+        }
+        stages
+    }
+    
+    /// Which stages the last one needs, directly or through other stages.
+    fn needed(stages: &[Stage]) -> Vec<bool> {
+        let names: Vec<Option<String>> = stages.iter().map(|stage| stage.name.clone()).collect();
+        let mut needed = vec![false; stages.len()];
+        if let Some(last) = needed.last_mut() {
+            *last = true;
+        }
+        // References only point backwards, so one pass from the end is enough
+        for i in (0..stages.len()).rev() {
+            if !needed[i] {
+                continue;
+            }
+            for instruction in &stages[i].instructions {
+                if let Instruction::Copy { from: Some(reference), .. } = instruction {
+                    if let Some(index) = novafile::stage_index(&names[..i], reference) {
+                        needed[index] = true;
+                    }
+                }
+            }
+        }
+        needed
+    }
+    
+    fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", index, name),
+            None => index.to_string(),
+        }
+    }
+}
+
+/// Files, layers and config of a stage built so far.
+struct StageState {
+    rootfs: PathBuf,
+    /// Scratch directory holding `rootfs` for stages other than the last.
+    _staging: Option<Staging>,
+    layers: Vec<Descriptor>,
+    metadata: ImageMetadata,
+    capabilities: Capabilities,
+    /// Key of the stage's build state; every step extends it.
+    key: String,
+    /// CMD came from the base image, so an ENTRYPOINT resets it.
+    inherited_cmd: bool,
+}
+
+impl StageState {
+    fn new(tag: &str, rootfs: PathBuf, staging: Option<Staging>) -> Self {
+        StageState {
+            rootfs,
+            _staging: staging,
+            layers: Vec::new(),
+            metadata: ImageMetadata {
+                tag: tag.to_string(),
+                base: String::from("scratch"),
+                env: Vec::new(),
+                entrypoint: Vec::new(),
+                cmd: Vec::new(),
+                expose: Vec::new(),
+                workdir: String::from("/"),
+                labels: Vec::new(),
+                secrets: Vec::new(),
+                init: None,
+                healthcheck: None,
+                volumes: Vec::new(),
+                resources: Resources::default(),
+                capabilities: None,
+            },
+            capabilities: Capabilities::default(),
+            key: String::new(),
+            inherited_cmd: false,
+        }
+    }
+}
+
+/// Where COPY reads files from: the build context or an earlier stage's files.
+struct Source<'a> {
+    /// Canonical, so symlinks can be checked against it.
+    root: &'a Path,
+    ignore: &'a NovaIgnore,
+    /// How errors refer to the source.
+    name: String,
+}
+
+impl Source<'_> {
+    /// Paths named by COPY sources, with globs expanded.
+    fn resolve(&self, sources: &[String]) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for src in sources {
+            let pattern = src.trim_start_matches("./").trim_start_matches('/');
+            if !pattern.contains(['*', '?', '[']) {
+                // Sources are relative to the root even when written as absolute paths
+                let src_path = self.root.join(layer::image_path(src)
+                    .with_context(|| format!("COPY failed: {} is outside the {}", src, self.name))?);
+                if fs::symlink_metadata(&src_path).is_err() {
+                    anyhow::bail!("COPY failed: {} not found in {}", src, self.name);
+                }
+                if self.ignore.is_ignored(&src_path, src_path.is_dir()) {
+                    anyhow::bail!("COPY failed: {} is excluded by .novaignore", src);
+                }
+                self.check_links(&src_path)?;
+                paths.push(src_path);
+                continue;
+            }
+            
+            // As in a shell, `*` does not cross directories; `**` does
+            let matcher = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid COPY pattern '{}'", src))?
+                .compile_matcher();
+            let matched = paths.len();
+            // A matched directory is copied whole, so nothing below it is matched again
+            let mut matched_dir: Option<PathBuf> = None;
+            for entry in self.ignore.walk(self.root, false) {
+                let entry = entry?;
+                if matched_dir.as_ref().is_some_and(|dir| entry.path().starts_with(dir)) {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(self.root)?;
+                if !relative.as_os_str().is_empty() && matcher.is_match(relative) {
+                    self.check_links(entry.path())?;
+                    if entry.file_type().is_dir() {
+                        matched_dir = Some(entry.path().to_path_buf());
+                    }
+                    paths.push(entry.into_path());
+                }
+            }
+            if paths.len() == matched {
+                anyhow::bail!("COPY failed: no files in {} match {}", self.name, src);
+            }
+        }
+        Ok(paths)
+    }
+    
+    /// Hash of the resolved sources' names and contents, for the step key.
+    fn hash(&self, inputs: &[PathBuf]) -> Result<String> {
+        let mut hashes = Vec::new();
+        for input in inputs {
+            let name = input.strip_prefix(self.root)?;
+            hashes.push(format!("{} {}", name.display(), cache::hash_path(input, self.ignore)?));
+        }
+        Ok(hashes.join("\n"))
+    }
+    
+    /// Symlinks are copied as what they point to, so the source (through
+    /// any linked parent directories) and every link in it must resolve to
+    /// a path inside the root.
+    fn check_links(&self, path: &Path) -> Result<()> {
+        self.confine(path)?;
+        for entry in self.ignore.walk(path, true) {
+            let entry = entry.map_err(|e| match e.path() {
+                Some(path) if path.is_symlink() => {
+                    let name = path.strip_prefix(self.root).unwrap_or(path);
+                    anyhow::anyhow!("COPY failed: {} is a broken or looping symlink", name.display())
+                }
+                _ => anyhow::anyhow!("COPY failed: {}", e),
+            })?;
+            if entry.path_is_symlink() {
+                self.confine(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Fail unless `path`, with every symlink on the way resolved, is inside the root.
+    fn confine(&self, path: &Path) -> Result<()> {
+        let name = path.strip_prefix(self.root).unwrap_or(path);
+        let target = fs::canonicalize(path)
+            .map_err(|_| anyhow::anyhow!("COPY failed: {} is a broken or looping symlink", name.display()))?;
+        if !target.starts_with(self.root) {
+            anyhow::bail!("COPY failed: {} resolves to {}, outside the {}", name.display(), target.display(), self.name);
+        }
+        Ok(())
+    }
+    
+    /// Copy a directory tree, leaving out what `.novaignore` excludes.
+    /// Symlinks are followed, so every entry is checked again in case a
+    /// link changed since `check_links`.
+    fn copy_dir(&self, src: &Path, dst: &Path) -> Result<()> {
+        for entry in self.ignore.walk(src, true) {
             let entry = entry?;
-            let src_path = entry.path();
-            let dst_path = dst.join(entry.file_name());
+            self.confine(entry.path())?;
+            let dst_path = dst.join(entry.path().strip_prefix(src)?);
             
-            if src_path.is_dir() {
-                Self::copy_dir(&src_path, &dst_path)?;
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&dst_path)?;
             } else {
-                fs::copy(&src_path, &dst_path)?;
+                fs::copy(entry.path(), &dst_path)
+                    .with_context(|| format!("COPY failed: {}", entry.path().display()))?;
             }
         }
         
@@ -219,13 +691,62 @@ impl ImageBuilder {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct ImageMetadata {
-    tag: String,
-    base: String,
-    env: Vec<(String, String)>,
-    cmd: Vec<String>,
-    expose: Vec<u16>,
-    workdir: String,
-    labels: Vec<(String, String)>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_sources_stay_in_context() {
+        let dir = std::env::temp_dir().join(format!("nova-context-{}", uuid::Uuid::new_v4()));
+        let context = dir.join("context");
+        fs::create_dir_all(context.join("site")).unwrap();
+        fs::write(dir.join("secret.txt"), b"secret").unwrap();
+        fs::write(context.join("app.wasm"), b"\0asm").unwrap();
+        fs::write(dir.join("Novafile"), "FROM scratch\n").unwrap();
+        std::os::unix::fs::symlink("../app.wasm", context.join("site/app.wasm")).unwrap();
+        let builder = ImageBuilder::new(&dir.join("Novafile"), &context, "test".into()).unwrap();
+
+        assert!(builder.context().resolve(&["site".into()]).is_ok());
+        assert!(builder.context().resolve(&["../secret.txt".into()]).is_err());
+
+        // A linked parent directory must not lead out of the context either
+        std::os::unix::fs::symlink("..", context.join("parent")).unwrap();
+        assert!(builder.context().resolve(&["parent/secret.txt".into()]).is_err());
+        assert!(builder.context().resolve(&["parent".into()]).is_err());
+        fs::remove_file(context.join("parent")).unwrap();
+        
+        std::os::unix::fs::symlink("../../secret.txt", context.join("site/secret.txt")).unwrap();
+        assert!(builder.context().resolve(&["site".into()]).is_err());
+        assert!(builder.context().resolve(&["*.wasm".into()]).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_globs_match_each_path_once() {
+        let dir = std::env::temp_dir().join(format!("nova-context-{}", uuid::Uuid::new_v4()));
+        let context = dir.join("context");
+        fs::create_dir_all(context.join("site/css")).unwrap();
+        fs::write(context.join("site/index.html"), b"<html>").unwrap();
+        fs::write(context.join("site/css/app.css"), b"body {}").unwrap();
+        fs::write(dir.join("Novafile"), "FROM scratch\n").unwrap();
+        let builder = ImageBuilder::new(&dir.join("Novafile"), &context, "test".into()).unwrap();
+
+        let paths = builder.context().resolve(&["site/**".into()]).unwrap();
+        let names: Vec<_> = paths.iter().map(|path| path.strip_prefix(&builder.context_dir).unwrap().to_path_buf()).collect();
+        assert_eq!(names, [PathBuf::from("site/css"), PathBuf::from("site/index.html")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_needs_allow_host_run() {
+        let dir = std::env::temp_dir().join(format!("nova-context-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Novafile"), "FROM scratch\nRUN touch escaped\n").unwrap();
+        let builder = ImageBuilder::new(&dir.join("Novafile"), &dir, "test".into()).unwrap();
+
+        let error = builder.build().unwrap_err().to_string();
+        assert!(error.contains("--allow-host-run"), "{}", error);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
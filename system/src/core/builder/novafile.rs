@@ -0,0 +1,708 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::core::capabilities::Capability;
+use crate::core::health::Healthcheck;
+use super::image::Resources;
+use super::layer;
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// Starts a build stage, optionally named with `AS`.
+    From { image: String, name: Option<String> },
+    /// `from` names an earlier stage to copy from instead of the build
+    /// context. `chown` is `(uid, gid)`, `chmod` the permission bits.
+    Copy { sources: Vec<String>, dst: String, from: Option<String>, chown: Option<(u64, u64)>, chmod: Option<u32> },
+    Env(Vec<(String, String)>),
+    /// A build argument and its default. Values are substituted while parsing.
+    Arg { key: String, default: Option<String> },
+    /// A command run at build time, with the `ARG` values it sees as
+    /// environment variables. The shell form is run as `/bin/sh -c`.
+    Run { command: Vec<String>, args: Vec<(String, String)> },
+    Entrypoint(Vec<String>),
+    Cmd(Vec<String>),
+    Expose(u16),
+    Workdir(String),
+    Label(Vec<(String, String)>),
+    Secret(String),
+    Init(String),
+    Capability(Capability),
+    /// `None` for `HEALTHCHECK NONE`.
+    Healthcheck(Option<Healthcheck>),
+    Volume(Vec<String>),
+    Resources(Resources),
+}
+
+#[derive(Debug)]
+pub struct Novafile {
+    pub instructions: Vec<Instruction>,
+}
+
+impl Novafile {
+    pub fn parse(path: &Path) -> Result<Self> {
+        Self::parse_with_args(path, &HashMap::new())
+    }
+
+    /// Parse with `--build-arg` values for the file's `ARG`s.
+    pub fn parse_with_args(path: &Path, build_args: &HashMap<String, String>) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .context("Failed to read Novafile")?;
+        Self::parse_str(&content, &path.display().to_string(), build_args)
+    }
+
+    /// Parse Novafile source; `file` names it in diagnostics.
+    pub fn parse_str(source: &str, file: &str, build_args: &HashMap<String, String>) -> Result<Self> {
+        let mut parser = Parser {
+            file,
+            lines: source.lines().collect(),
+            build_args,
+            global_args: HashMap::new(),
+            args: HashMap::new(),
+            env: HashMap::new(),
+            stages: Vec::new(),
+            implicit_stage: false,
+        };
+
+        let mut instructions = Vec::new();
+        for line in logical_lines(source) {
+            instructions.push(parser.instruction(&line)?);
+        }
+
+        Ok(Novafile { instructions })
+    }
+}
+
+/// Index of the stage `reference` names: an `AS` name or a 0-based index.
+/// `stages` holds the names of the stages it may refer to.
+pub fn stage_index(stages: &[Option<String>], reference: &str) -> Option<usize> {
+    match reference.parse::<usize>() {
+        Ok(index) => (index < stages.len()).then_some(index),
+        Err(_) => stages.iter().position(|name| name.as_deref() == Some(&reference.to_lowercase())),
+    }
+}
+
+/// A parse error, shown with the line it is about:
+///
+/// ```text
+/// Novafile:3:8: EXPOSE requires a valid port number
+///   |
+/// 3 | EXPOSE http
+///   |        ^^^^
+/// ```
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    source_line: String,
+    width: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let pad = " ".repeat(number.len());
+        writeln!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", number, self.source_line)?;
+        write!(f, "{} | {}{}", pad, " ".repeat(self.column - 1), "^".repeat(self.width.max(1)))
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// 1-based line and column of a character in the source.
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+/// First and last character of a piece of an instruction.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: Pos,
+    end: Pos,
+}
+
+/// One instruction with `\` continuations joined. Every character keeps its
+/// source position so errors can point at it.
+type Line = Vec<(char, Pos)>;
+
+/// A word of an instruction, with quotes removed and variables substituted.
+struct Word {
+    text: String,
+    span: Span,
+    /// Index just past the word in the characters it was read from.
+    end: usize,
+}
+
+fn logical_lines(source: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut current: Option<Line> = None;
+
+    for (i, raw) in source.lines().enumerate() {
+        // Comments and blank lines are skipped, also between continued lines
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let body = raw.trim_end();
+        let (body, continues) = match body.strip_suffix('\\') {
+            Some(body) => (body, true),
+            None => (body, false),
+        };
+        let line = current.get_or_insert_with(Vec::new);
+        for (column, c) in body.chars().enumerate() {
+            line.push((c, Pos { line: i + 1, column: column + 1 }));
+        }
+        if !continues {
+            lines.extend(current.take());
+        }
+    }
+
+    lines.extend(current);
+    // A lone `\` continues nothing
+    lines.retain(|line| line.iter().any(|(c, _)| !c.is_whitespace()));
+    lines
+}
+
+fn span_of(chars: &[(char, Pos)]) -> Span {
+    Span { start: chars[0].1, end: chars[chars.len() - 1].1 }
+}
+
+fn text_of(chars: &[(char, Pos)]) -> String {
+    chars.iter().map(|(c, _)| c).collect()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+struct Parser<'a> {
+    file: &'a str,
+    lines: Vec<&'a str>,
+    build_args: &'a HashMap<String, String>,
+    /// `ARG`s declared before the first `FROM`. Only `FROM` lines see them;
+    /// a stage uses one by declaring it again.
+    global_args: HashMap<String, String>,
+    /// Values of the `ARG`s declared so far in the current stage.
+    args: HashMap<String, String>,
+    /// Variables set by `ENV` so far in the current stage; they take
+    /// precedence over `ARG`s.
+    env: HashMap<String, String>,
+    /// Names of the stages so far. Instructions before any `FROM` form an
+    /// unnamed stage starting from scratch.
+    stages: Vec<Option<String>>,
+    implicit_stage: bool,
+}
+
+impl Parser<'_> {
+    fn error(&self, span: Span, message: impl Into<String>) -> anyhow::Error {
+        let source_line = self.lines[span.start.line - 1].to_string();
+        let width = if span.end.line == span.start.line {
+            span.end.column + 1 - span.start.column
+        } else {
+            source_line.trim_end().chars().count() + 1 - span.start.column
+        };
+        anyhow::Error::new(Diagnostic {
+            file: self.file.to_string(),
+            line: span.start.line,
+            column: span.start.column,
+            message: message.into(),
+            source_line,
+            width,
+        })
+    }
+
+    fn instruction(&mut self, line: &[(char, Pos)]) -> Result<Instruction> {
+        let start = line.iter().position(|(c, _)| !c.is_whitespace()).unwrap_or(0);
+        let line = &line[start..];
+        let split = line.iter().position(|(c, _)| c.is_whitespace()).unwrap_or(line.len());
+        let (keyword, args) = line.split_at(split);
+        let keyword_span = span_of(keyword);
+        let cmd = text_of(keyword).to_uppercase();
+
+        let args_start = args.iter().position(|(c, _)| !c.is_whitespace()).unwrap_or(args.len());
+        let args = &args[args_start..];
+        if args.is_empty() {
+            return Err(self.error(keyword_span, format!("{} requires arguments", cmd)));
+        }
+        let args_span = span_of(args);
+
+        if self.stages.is_empty() && !matches!(cmd.as_str(), "FROM" | "ARG") {
+            self.stages.push(None);
+            self.implicit_stage = true;
+        }
+
+        match cmd.as_str() {
+            "FROM" => {
+                if self.implicit_stage {
+                    return Err(self.error(keyword_span, "FROM must come before other instructions"));
+                }
+                self.env.clear();
+                self.args = self.global_args.clone();
+                let words = self.words(args, true)?;
+                self.args.clear();
+
+                let (image, name) = match words.as_slice() {
+                    [image] => (image.text.clone(), None),
+                    [image, keyword, name] if keyword.text.eq_ignore_ascii_case("as") => {
+                        let valid = name.text.starts_with(|c: char| c.is_ascii_alphabetic())
+                            && name.text.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+                        if !valid {
+                            return Err(self.error(name.span, format!("Invalid stage name '{}'", name.text)));
+                        }
+                        let name = name.text.to_lowercase();
+                        if stage_index(&self.stages, &name).is_some() {
+                            return Err(self.error(words[2].span, format!("Stage '{}' is already defined", name)));
+                        }
+                        (image.text.clone(), Some(name))
+                    }
+                    _ => return Err(self.error(args_span, "FROM takes an image and an optional AS <name>")),
+                };
+                self.stages.push(name.clone());
+                Ok(Instruction::From { image, name })
+            }
+
+            "COPY" => {
+                // Leading --flag=value options, then the paths
+                let words = self.words(args, true)?;
+                let mut rest = 0;
+                let mut from = None;
+                let mut chown = None;
+                let mut chmod = None;
+                for word in words.iter().take_while(|word| word.text.starts_with("--")) {
+                    match word.text.split_once('=') {
+                        Some(("--from", stage)) => {
+                            // Only earlier stages can be copied from
+                            let earlier = &self.stages[..self.stages.len() - 1];
+                            if stage_index(earlier, stage).is_none() {
+                                return Err(self.error(word.span, format!("COPY --from={} does not name an earlier stage", stage)));
+                            }
+                            from = Some(stage.to_lowercase());
+                        }
+                        Some(("--chown", owner)) => {
+                            chown = Some(parse_chown(owner).map_err(|e| self.error(word.span, e.to_string()))?);
+                        }
+                        Some(("--chmod", mode)) => {
+                            let mode = u32::from_str_radix(mode, 8).ok()
+                                .filter(|mode| *mode <= 0o7777)
+                                .ok_or_else(|| self.error(word.span, format!("--chmod requires octal permissions, got '{}'", mode)))?;
+                            chmod = Some(mode);
+                        }
+                        _ => return Err(self.error(word.span, format!("Unknown COPY option: {}", word.text))),
+                    }
+                    rest = word.end;
+                }
+
+                // JSON array form allows paths with spaces: ["my app.wasm", "/app/"]
+                let paths = &args[rest..];
+                let paths = &paths[paths.iter().position(|(c, _)| !c.is_whitespace()).unwrap_or(paths.len())..];
+                let (mut sources, span) = if paths.first().is_some_and(|(c, _)| *c == '[') {
+                    let sources: Vec<String> = serde_json::from_str(&text_of(paths))
+                        .map_err(|e| self.error(span_of(paths), format!("Failed to parse COPY as JSON array: {}", e)))?;
+                    (sources, span_of(paths))
+                } else {
+                    let words = self.words(paths, true)?;
+                    let span = words.last().map_or(args_span, |word| word.span);
+                    (words.into_iter().map(|word| word.text).collect(), span)
+                };
+
+                if sources.len() < 2 {
+                    return Err(self.error(args_span, "COPY requires source and destination"));
+                }
+                let dst = sources.pop().unwrap();
+                if sources.len() > 1 && !dst.ends_with('/') {
+                    return Err(self.error(span, "COPY with more than one source requires a destination directory ending in '/'"));
+                }
+                Ok(Instruction::Copy { sources, dst, from, chown, chmod })
+            }
+
+            "ENV" => {
+                let pairs = self.pairs(&cmd, args)?;
+                for (key, value) in &pairs {
+                    self.env.insert(key.clone(), value.clone());
+                }
+                Ok(Instruction::Env(pairs))
+            }
+
+            "ARG" => {
+                let word = self.single(&cmd, args)?;
+                let (key, default) = match word.text.split_once('=') {
+                    Some((key, default)) => (key.to_string(), Some(default.to_string())),
+                    None => (word.text.clone(), None),
+                };
+                if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) || !key.chars().all(is_name_char) {
+                    return Err(self.error(word.span, format!("Invalid ARG name '{}'", key)));
+                }
+                // Inside a stage, an ARG without a default takes the global one
+                let global = if self.stages.is_empty() { None } else { self.global_args.get(&key) };
+                if let Some(value) = self.build_args.get(&key).or(default.as_ref()).or(global) {
+                    self.args.insert(key.clone(), value.clone());
+                    if self.stages.is_empty() {
+                        self.global_args.insert(key.clone(), value.clone());
+                    }
+                }
+                Ok(Instruction::Arg { key, default })
+            }
+
+            "RUN" => {
+                let command = if args[0].0 == '[' {
+                    serde_json::from_str(&text_of(args))
+                        .map_err(|e| self.error(args_span, format!("Failed to parse RUN as JSON array: {}", e)))?
+                } else {
+                    // The shell does its own quoting and substitution
+                    vec!["/bin/sh".to_string(), "-c".to_string(), text_of(args)]
+                };
+                let mut run_args: Vec<(String, String)> = self.args.clone().into_iter().collect();
+                run_args.sort();
+                Ok(Instruction::Run { command, args: run_args })
+            }
+
+            "ENTRYPOINT" => Ok(Instruction::Entrypoint(self.command(&cmd, args)?)),
+
+            "CMD" => Ok(Instruction::Cmd(self.command(&cmd, args)?)),
+
+            "EXPOSE" => {
+                let word = self.single(&cmd, args)?;
+                let port: u16 = word.text.parse()
+                    .map_err(|_| self.error(word.span, "EXPOSE requires a valid port number"))?;
+                Ok(Instruction::Expose(port))
+            }
+
+            "WORKDIR" => Ok(Instruction::Workdir(self.single(&cmd, args)?.text)),
+
+            "LABEL" => Ok(Instruction::Label(self.pairs(&cmd, args)?)),
+
+            "SECRET" => {
+                let word = self.single(&cmd, args)?;
+                crate::core::secrets::validate_name(&word.text)
+                    .map_err(|e| self.error(word.span, e.to_string()))?;
+                Ok(Instruction::Secret(word.text))
+            }
+
+            "INIT" => Ok(Instruction::Init(self.single(&cmd, args)?.text)),
+
+            "CAPABILITY" => {
+                let words: Vec<String> = self.words(args, true)?.into_iter().map(|word| word.text).collect();
+                let capability = Capability::parse(&words.join(" "))
+                    .map_err(|e| self.error(args_span, e.to_string()))?;
+                Ok(Instruction::Capability(capability))
+            }
+
+            "HEALTHCHECK" => {
+                let words: Vec<String> = self.words(args, true)?.into_iter().map(|word| word.text).collect();
+                let check = Healthcheck::parse(&words)
+                    .map_err(|e| self.error(args_span, e.to_string()))?;
+                Ok(Instruction::Healthcheck(check))
+            }
+
+            "VOLUME" => {
+                let paths: Vec<String> = if args[0].0 == '[' {
+                    serde_json::from_str(&text_of(args))
+                        .map_err(|e| self.error(args_span, format!("Failed to parse VOLUME as JSON array: {}", e)))?
+                } else {
+                    self.words(args, true)?.into_iter().map(|word| word.text).collect()
+                };
+                for path in &paths {
+                    if !path.starts_with('/') || layer::image_path(path).is_err() {
+                        return Err(self.error(args_span, format!("VOLUME expects absolute paths in the image, got '{}'", path)));
+                    }
+                }
+                Ok(Instruction::Volume(paths))
+            }
+
+            "RESOURCES" => {
+                let mut resources = Resources::default();
+                for (key, value) in self.pairs(&cmd, args)? {
+                    let number = value.parse().ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| self.error(args_span, format!("RESOURCES {} expects a positive number, got '{}'", key, value)))?;
+                    match key.to_lowercase().as_str() {
+                        "memory" => resources.memory_mb = Some(number),
+                        "fuel" => resources.fuel = Some(number),
+                        _ => return Err(self.error(args_span, format!("Unknown resource '{}' (expected memory or fuel)", key))),
+                    }
+                }
+                Ok(Instruction::Resources(resources))
+            }
+
+            _ => Err(self.error(keyword_span, format!("Unknown instruction: {}", cmd))),
+        }
+    }
+
+    /// A command as a JSON array, `["/app/app.wasm", "serve"]`, or as words.
+    fn command(&self, cmd: &str, args: &[(char, Pos)]) -> Result<Vec<String>> {
+        if args[0].0 == '[' {
+            return serde_json::from_str(&text_of(args))
+                .map_err(|e| self.error(span_of(args), format!("Failed to parse {} as JSON array: {}", cmd, e)));
+        }
+        // Split into words, honouring quotes
+        Ok(self.words(args, false)?.into_iter().map(|word| word.text).collect())
+    }
+
+    /// The one word an instruction takes.
+    fn single(&self, cmd: &str, args: &[(char, Pos)]) -> Result<Word> {
+        let mut words = self.words(args, true)?;
+        if words.len() > 1 {
+            return Err(self.error(words[1].span, format!("{} takes a single argument", cmd)));
+        }
+        Ok(words.remove(0))
+    }
+
+    /// `KEY=VALUE` pairs, or the older `KEY VALUE...` form with a single key.
+    fn pairs(&self, cmd: &str, args: &[(char, Pos)]) -> Result<Vec<(String, String)>> {
+        let words = self.words(args, true)?;
+        if !words[0].text.contains('=') {
+            if words.len() < 2 {
+                return Err(self.error(words[0].span, format!("{} requires KEY=VALUE format", cmd)));
+            }
+            let value: Vec<&str> = words[1..].iter().map(|word| word.text.as_str()).collect();
+            return Ok(vec![(words[0].text.clone(), value.join(" "))]);
+        }
+
+        words.iter()
+            .map(|word| match word.text.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                _ => Err(self.error(word.span, format!("{} requires KEY=VALUE format", cmd))),
+            })
+            .collect()
+    }
+
+    /// Split into words. Single quotes are literal; double quotes allow `\"`,
+    /// `\\` and `\$`; outside quotes `\` escapes the next character. With
+    /// `expand`, `$VAR`, `${VAR}`, `${VAR:-default}` and `${VAR:+alternative}`
+    /// are substituted outside single quotes.
+    fn words(&self, chars: &[(char, Pos)], expand: bool) -> Result<Vec<Word>> {
+        let mut words = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].0.is_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let start = chars[i].1;
+            let mut text = String::new();
+            while i < chars.len() && !chars[i].0.is_whitespace() {
+                match chars[i].0 {
+                    quote @ ('\'' | '"') => {
+                        let open = i;
+                        i += 1;
+                        loop {
+                            let Some(&(c, _)) = chars.get(i) else {
+                                return Err(self.error(span_of(&chars[open..]), "Unterminated quote"));
+                            };
+                            match c {
+                                c if c == quote => {
+                                    i += 1;
+                                    break;
+                                }
+                                '\\' if quote == '"' && matches!(chars.get(i + 1), Some(('"' | '\\' | '$', _))) => {
+                                    text.push(chars[i + 1].0);
+                                    i += 2;
+                                }
+                                '$' if quote == '"' && expand => i = self.expand(chars, i, &mut text)?,
+                                c => {
+                                    text.push(c);
+                                    i += 1;
+                                }
+                            }
+                        }
+                    }
+                    '\\' if i + 1 < chars.len() => {
+                        text.push(chars[i + 1].0);
+                        i += 2;
+                    }
+                    '$' if expand => i = self.expand(chars, i, &mut text)?,
+                    c => {
+                        text.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            words.push(Word { text, span: Span { start, end: chars[i - 1].1 }, end: i });
+        }
+        Ok(words)
+    }
+
+    /// Substitute the variable reference starting at `chars[i]` (a `$`) into
+    /// `text`. Returns the index after it. Unset variables are empty.
+    fn expand(&self, chars: &[(char, Pos)], i: usize, text: &mut String) -> Result<usize> {
+        let lookup = |name: &str| self.env.get(name).or_else(|| self.args.get(name)).cloned();
+
+        match chars.get(i + 1).map(|(c, _)| *c) {
+            Some('{') => {
+                let close = chars[i + 2..].iter().position(|(c, _)| *c == '}')
+                    .map(|p| i + 2 + p)
+                    .ok_or_else(|| self.error(span_of(&chars[i..]), "Unterminated ${"))?;
+                let inner = text_of(&chars[i + 2..close]);
+                let name_len = inner.find(|c: char| !is_name_char(c)).unwrap_or(inner.len());
+                let (name, modifier) = inner.split_at(name_len);
+                let value = lookup(name).filter(|value| !value.is_empty());
+
+                let substituted = match (modifier.get(..2), value) {
+                    _ if name.is_empty() => None,
+                    (None, value) => Some(value.unwrap_or_default()),
+                    (Some(":-"), value) => Some(value.unwrap_or_else(|| modifier[2..].to_string())),
+                    (Some(":+"), value) => Some(value.map(|_| modifier[2..].to_string()).unwrap_or_default()),
+                    _ => None,
+                };
+                let substituted = substituted
+                    .ok_or_else(|| self.error(span_of(&chars[i..=close]), format!("Invalid substitution ${{{}}}", inner)))?;
+                text.push_str(&substituted);
+                Ok(close + 1)
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let end = chars[i + 1..].iter().position(|(c, _)| !is_name_char(*c))
+                    .map_or(chars.len(), |p| i + 1 + p);
+                text.push_str(&lookup(&text_of(&chars[i + 1..end])).unwrap_or_default());
+                Ok(end)
+            }
+            _ => {
+                text.push('$');
+                Ok(i + 1)
+            }
+        }
+    }
+}
+
+/// `uid[:gid]`; the group defaults to the user id. Wasm images have no
+/// user database, so names are not accepted.
+fn parse_chown(owner: &str) -> Result<(u64, u64)> {
+    let (uid, gid) = owner.split_once(':').unwrap_or((owner, owner));
+    match (uid.parse(), gid.parse()) {
+        (Ok(uid), Ok(gid)) => Ok((uid, gid)),
+        _ => anyhow::bail!("--chown requires numeric uid[:gid], got '{}'", owner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Vec<Instruction>> {
+        let build_args = HashMap::from([("VERSION".to_string(), "2.0".to_string())]);
+        Ok(Novafile::parse_str(source, "Novafile", &build_args)?.instructions)
+    }
+
+    #[test]
+    fn test_parse_copy_forms() {
+        let Instruction::Copy { sources, dst, chown, chmod, .. } =
+            parse(r#"COPY --chown=1000:100 --chmod=755 ["my app.wasm", "*.html", "/app/"]"#).unwrap().remove(0)
+        else {
+            panic!("expected COPY");
+        };
+        assert_eq!(sources, ["my app.wasm", "*.html"]);
+        assert_eq!(dst, "/app/");
+        assert_eq!(chown, Some((1000, 100)));
+        assert_eq!(chmod, Some(0o755));
+
+        assert!(parse("COPY a.wasm b.wasm /app").is_err());
+        assert!(parse("COPY --chown=nova a.wasm /app/").is_err());
+    }
+
+    #[test]
+    fn test_continuations_quotes_and_variables() {
+        let instructions = parse(concat!(
+            "ARG VERSION=1.0\n",
+            "ARG NAME=app\n",
+            "ENV APP_DIR=/srv/$NAME \\\n",
+            "    # comments inside continuations are skipped\n",
+            "    TITLE=\"My ${NAME}\" RAW='$NAME'\n",
+            "COPY ${NAME}-${VERSION}.wasm ${APP_DIR}/\n",
+            "LABEL version=${VERSION} channel=${CHANNEL:-stable}\n",
+            "CMD run \"$NAME\" 'a b'\n",
+            "RUN cargo build \\\n    --release\n",
+        )).unwrap();
+
+        let Instruction::Env(env) = &instructions[2] else { panic!("expected ENV") };
+        assert_eq!(env, &[
+            ("APP_DIR".to_string(), "/srv/app".to_string()),
+            ("TITLE".to_string(), "My app".to_string()),
+            ("RAW".to_string(), "$NAME".to_string()),
+        ]);
+        let Instruction::Copy { sources, dst, .. } = &instructions[3] else { panic!("expected COPY") };
+        assert_eq!((sources[0].as_str(), dst.as_str()), ("app-2.0.wasm", "/srv/app/"));
+        let Instruction::Label(labels) = &instructions[4] else { panic!("expected LABEL") };
+        assert_eq!(labels[1], ("channel".to_string(), "stable".to_string()));
+        let Instruction::Cmd(cmd) = &instructions[5] else { panic!("expected CMD") };
+        assert_eq!(cmd, &["run", "$NAME", "a b"]);
+        let Instruction::Run { command, args } = &instructions[6] else { panic!("expected RUN") };
+        assert_eq!(command[2], "cargo build     --release");
+        assert_eq!(args[0], ("NAME".to_string(), "app".to_string()));
+    }
+
+    #[test]
+    fn test_diagnostics_point_at_the_error() {
+        let error = parse("FROM scratch\n\n# port\nEXPOSE http\n").unwrap_err();
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!((diagnostic.line, diagnostic.column), (4, 8));
+        assert!(error.to_string().ends_with("4 | EXPOSE http\n  |        ^^^^"));
+
+        let error = parse("ENV A=1 \\\n    B\n").unwrap_err();
+        assert_eq!(error.downcast_ref::<Diagnostic>().unwrap().line, 2);
+        assert!(parse("COPY \"app.wasm /app\n").is_err());
+        assert!(parse("ENV A=${B\n").is_err());
+
+        // Empty continued lines are skipped, not parsed as instructions
+        assert_eq!(parse("FROM scratch\n\\\n").unwrap().len(), 1);
+        assert_eq!(parse("FROM scratch\n  \\\n\\\nEXPOSE 80\n").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_stages_and_arg_scope() {
+        let instructions = parse(concat!(
+            "ARG VERSION=1.0\n",
+            "FROM rust:${VERSION} AS Builder\n",
+            "ENV TARGET=wasm32-wasip1\n",
+            "COPY app-$VERSION.wasm /out/\n",
+            "FROM scratch\n",
+            "ARG VERSION\n",
+            "COPY --from=builder /out/app-${VERSION}${TARGET}.wasm /app/\n",
+            "COPY --from=0 /out /out\n",
+        )).unwrap();
+
+        let Instruction::From { image, name } = &instructions[1] else { panic!("expected FROM") };
+        assert_eq!((image.as_str(), name.as_deref()), ("rust:2.0", Some("builder")));
+        // A stage sees global ARGs only after declaring them, and no earlier ENV
+        let Instruction::Copy { sources, .. } = &instructions[3] else { panic!("expected COPY") };
+        assert_eq!(sources[0], "app-.wasm");
+        let Instruction::Copy { sources, from, .. } = &instructions[6] else { panic!("expected COPY") };
+        assert_eq!((sources[0].as_str(), from.as_deref()), ("/out/app-2.0.wasm", Some("builder")));
+        assert_eq!(stage_index(&[Some("builder".into()), None], "0"), Some(0));
+
+        assert!(parse("FROM scratch\nCOPY --from=builder /a /b\n").is_err());
+        assert!(parse("FROM scratch AS a\nCOPY --from=a /a /b\n").is_err());
+        assert!(parse("FROM scratch AS a\nFROM scratch AS A\n").is_err());
+        assert!(parse("COPY a /b\nFROM scratch\n").is_err());
+    }
+
+    #[test]
+    fn test_runtime_instructions() {
+        let instructions = parse(concat!(
+            "ENTRYPOINT [\"/app/app.wasm\", \"serve\"]\n",
+            "HEALTHCHECK --interval=5s http 8080 /healthz\n",
+            "HEALTHCHECK NONE\n",
+            "VOLUME /data /cache\n",
+            "RESOURCES memory=256 fuel=1000000\n",
+        )).unwrap();
+
+        assert!(matches!(&instructions[0], Instruction::Entrypoint(command) if command == &["/app/app.wasm", "serve"]));
+        assert!(matches!(&instructions[1], Instruction::Healthcheck(Some(check)) if check.interval_secs == 5));
+        assert!(matches!(&instructions[2], Instruction::Healthcheck(None)));
+        assert!(matches!(&instructions[3], Instruction::Volume(paths) if paths == &["/data", "/cache"]));
+        assert!(matches!(&instructions[4], Instruction::Resources(Resources { memory_mb: Some(256), fuel: Some(1000000) })));
+
+        assert!(parse("VOLUME data").is_err());
+        assert!(parse("VOLUME /../data").is_err());
+        assert!(parse("RESOURCES cpu=2").is_err());
+        assert!(parse("RESOURCES memory=0").is_err());
+        assert!(parse("HEALTHCHECK --retries=x export health").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use walkdir::WalkDir;
+use super::layer::Staging;
+
+/// Host variables a RUN step keeps so toolchains can find themselves. The
+/// rest of the host environment is not passed on.
+const PASS_THROUGH: &[&str] = &[
+    "PATH", "HOME", "USER", "LANG", "TMPDIR",
+    "CARGO_HOME", "RUSTUP_HOME", "GOPATH", "GOROOT", "GOCACHE", "GOMODCACHE",
+];
+
+/// Run `command` with the host toolchain in a scratch copy of `rootfs`,
+/// starting in `workdir`, and stage the files it added or changed as a layer.
+/// `NOVA_ROOT` points the command at the copy's root.
+pub fn run(rootfs: &Path, workdir: &str, command: &[String], env: &[(String, String)]) -> Result<Staging> {
+    let sandbox = Staging::new()?;
+    copy_tree(rootfs, &sandbox.dir)?;
+    let cwd = sandbox.join(workdir)?;
+    fs::create_dir_all(&cwd)?;
+
+    let (program, args) = command.split_first()
+        .ok_or_else(|| anyhow::anyhow!("RUN requires a command"))?;
+    let mut process = Command::new(program);
+    process.args(args).current_dir(&cwd).env_clear();
+    for name in PASS_THROUGH {
+        if let Ok(value) = std::env::var(name) {
+            process.env(name, value);
+        }
+    }
+    process.envs(env.iter().map(|(key, value)| (key, value)));
+    process.env("NOVA_ROOT", &sandbox.dir);
+
+    let status = process.status()
+        .with_context(|| format!("RUN failed to start {}", program))?;
+    if !status.success() {
+        anyhow::bail!("RUN failed: {} ({})", command.join(" "), status);
+    }
+
+    // Only what the command added or changed goes into the layer
+    let changes = Staging::new()?;
+    for entry in WalkDir::new(&sandbox.dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(&sandbox.dir)?;
+        let before = rootfs.join(relative);
+        let target = changes.dir.join(relative);
+
+        if entry.file_type().is_dir() {
+            if !before.is_dir() {
+                fs::create_dir_all(&target)?;
+            }
+        } else if entry.file_type().is_file() {
+            if !unchanged(&before, entry.path())? {
+                fs::create_dir_all(target.parent().unwrap())?;
+                fs::copy(entry.path(), &target)?;
+            }
+        } else {
+            println!(" ---> Skipping {}: layers hold only files and directories", relative.display());
+        }
+    }
+    Ok(changes)
+}
+
+/// Whether `after` has the same content and permissions as `before`.
+fn unchanged(before: &Path, after: &Path) -> Result<bool> {
+    let (Ok(old), Ok(new)) = (fs::symlink_metadata(before), fs::metadata(after)) else {
+        return Ok(false);
+    };
+    if !old.is_file() || old.len() != new.len() || old.permissions() != new.permissions() {
+        return Ok(false);
+    }
+    Ok(fs::read(before)? == fs::read(after)?)
+}
+
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    for entry in WalkDir::new(src).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let target = dst.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_stages_only_changes() {
+        let rootfs = Staging::new().unwrap();
+        fs::create_dir_all(rootfs.join("/src").unwrap()).unwrap();
+        fs::write(rootfs.join("/src/main.rs").unwrap(), b"fn main() {}").unwrap();
+        fs::write(rootfs.join("/src/old.txt").unwrap(), b"old").unwrap();
+
+        let command = ["sh", "-c", "cat main.rs > out.txt && echo $GREETING > old.txt && mkdir -p empty"].map(String::from);
+        let env = [("GREETING".to_string(), "new".to_string())];
+        let changes = run(&rootfs.dir, "/src", &command, &env).unwrap();
+
+        assert_eq!(fs::read(changes.join("/src/out.txt").unwrap()).unwrap(), b"fn main() {}");
+        assert_eq!(fs::read(changes.join("/src/old.txt").unwrap()).unwrap(), b"new\n");
+        assert!(changes.join("/src/empty").unwrap().is_dir());
+        assert!(!changes.join("/src/main.rs").unwrap().exists());
+
+        let failing = ["sh", "-c", "exit 3"].map(String::from);
+        assert!(run(&rootfs.dir, "/", &failing, &[]).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_MEDIA_TYPE: &str = "application/vnd.nova.image.manifest.v1+json";
+pub const CONFIG_MEDIA_TYPE: &str = "application/vnd.nova.image.config.v1+json";
+pub const LAYER_MEDIA_TYPE: &str = "application/vnd.nova.image.layer.v1.tar";
+
+/// `sha256:<hex>` of `bytes`.
+pub fn digest(bytes: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+/// A blob referenced by a manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+}
+
+/// An image: its config blob (`ImageMetadata` as JSON) and layers, applied
+/// in order onto an empty root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub media_type: String,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+}
+
+impl Manifest {
+    pub fn new(config: Descriptor, layers: Vec<Descriptor>) -> Self {
+        Manifest { schema_version: 1, media_type: MANIFEST_MEDIA_TYPE.to_string(), config, layers }
+    }
+
+    /// Bytes as stored in the blob store; the manifest's digest is their hash.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Content-addressed blobs under `<root>/sha256/<hex>`. Each blob is written
+/// once, however many images share it.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    /// The store of the current project, `.nova/blobs`.
+    pub fn local() -> Self {
+        Self::at(".nova/blobs")
+    }
+
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        BlobStore { root: root.into() }
+    }
+
+    pub fn path(&self, digest: &str) -> Result<PathBuf> {
+        let hex = digest.strip_prefix("sha256:")
+            .filter(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| anyhow::anyhow!("Invalid digest '{}'", digest))?;
+        Ok(self.root.join("sha256").join(hex))
+    }
+
+    pub fn contains(&self, digest: &str) -> bool {
+        self.path(digest).is_ok_and(|p| p.exists())
+    }
+
+    /// Store `bytes` and describe them. Existing blobs are not rewritten.
+    pub fn put(&self, media_type: &str, bytes: &[u8]) -> Result<Descriptor> {
+        let digest = digest(bytes);
+        let path = self.path(&digest)?;
+        if !path.exists() {
+            fs::create_dir_all(path.parent().unwrap())?;
+            // Write then rename so a crash never leaves a truncated blob behind
+            let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            fs::write(&tmp, bytes)?;
+            fs::rename(&tmp, &path)?;
+        }
+        Ok(Descriptor { media_type: media_type.to_string(), digest, size: bytes.len() as u64 })
+    }
+
+    /// Read a blob, failing if its content does not match its digest.
+    pub fn get(&self, descriptor: &Descriptor) -> Result<Vec<u8>> {
+        let path = self.path(&descriptor.digest)?;
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Blob {} is missing from {}", descriptor.digest, self.root.display()))?;
+        let actual = digest(&bytes);
+        if actual != descriptor.digest || bytes.len() as u64 != descriptor.size {
+            anyhow::bail!("Blob {} is corrupt (content hashes to {})", descriptor.digest, actual);
+        }
+        Ok(bytes)
+    }
+
+    /// Copy a blob from another store, verifying it. Returns false if it was already here.
+    pub fn copy_from(&self, other: &BlobStore, descriptor: &Descriptor) -> Result<bool> {
+        if self.contains(&descriptor.digest) {
+            return Ok(false);
+        }
+        let bytes = other.get(descriptor)?;
+        self.put(&descriptor.media_type, &bytes)?;
+        Ok(true)
+    }
+}
+
+/// Read a manifest file. Unknown fields (e.g. registry push info) are ignored.
+pub fn read_manifest(path: &Path) -> Result<Manifest> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blobs_are_deduplicated_and_verified() {
+        let root = std::env::temp_dir().join(format!("nova-blobs-{}", uuid::Uuid::new_v4()));
+        let store = BlobStore::at(&root);
+
+        let a = store.put(LAYER_MEDIA_TYPE, b"layer").unwrap();
+        let b = store.put(LAYER_MEDIA_TYPE, b"layer").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(fs::read_dir(root.join("sha256")).unwrap().count(), 1);
+        assert_eq!(store.get(&a).unwrap(), b"layer");
+
+        fs::write(store.path(&a.digest).unwrap(), b"tampered").unwrap();
+        assert!(store.get(&a).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
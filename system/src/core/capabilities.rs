@@ -0,0 +1,249 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use crate::core::http::HttpRule;
+use crate::core::runtime::RunConfig;
+
+/// One `CAPABILITY` line of a Novafile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Capability {
+    /// Guest paths that may be backed by a `--map-dir` mount.
+    Mount(Vec<String>),
+    /// Environment variable names the container may receive.
+    Env(Vec<String>),
+    /// `--allow-http` rules the container may be granted.
+    Network(Vec<String>),
+    /// Peer pids `nova_send` may reach, or `*` for every replica.
+    Mesh(Vec<String>),
+    MaxMemoryMb(u64),
+    MaxFuel(u64),
+}
+
+impl Capability {
+    /// Parse `<kind> <value>...`, e.g. `network api.example.com=GET,POST`.
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut parts = args.split_whitespace();
+        let kind = parts.next()
+            .ok_or_else(|| anyhow::anyhow!("CAPABILITY requires a kind and values"))?
+            .to_lowercase();
+        let values: Vec<String> = parts.map(String::from).collect();
+        if values.is_empty() {
+            anyhow::bail!("CAPABILITY {} requires at least one value", kind);
+        }
+
+        let single = |values: &[String]| -> Result<u64> {
+            match values {
+                [value] => value.parse()
+                    .map_err(|_| anyhow::anyhow!("CAPABILITY {} expects a number, got '{}'", kind, value)),
+                _ => anyhow::bail!("CAPABILITY {} takes exactly one value", kind),
+            }
+        };
+
+        match kind.as_str() {
+            "mount" => {
+                for path in &values {
+                    if !path.starts_with('/') {
+                        anyhow::bail!("CAPABILITY mount expects absolute guest paths, got '{}'", path);
+                    }
+                }
+                Ok(Capability::Mount(values))
+            }
+            "env" => Ok(Capability::Env(values)),
+            "network" => {
+                for rule in &values {
+                    HttpRule::parse(rule)?;
+                }
+                Ok(Capability::Network(values))
+            }
+            "mesh" => {
+                for peer in &values {
+                    if peer != "*" && peer.parse::<u32>().is_err() {
+                        anyhow::bail!("CAPABILITY mesh expects peer pids or '*', got '{}'", peer);
+                    }
+                }
+                Ok(Capability::Mesh(values))
+            }
+            "memory" => Ok(Capability::MaxMemoryMb(single(&values)?)),
+            "fuel" => Ok(Capability::MaxFuel(single(&values)?)),
+            _ => anyhow::bail!("Unknown capability '{}' (expected mount, env, network, mesh, memory or fuel)", kind),
+        }
+    }
+}
+
+/// Everything a container built from an image may be granted. Stored in
+/// `ImageMetadata`; `nova run` refuses grants outside it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    #[serde(default)]
+    pub mounts: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default)]
+    pub network: Vec<String>,
+    #[serde(default)]
+    pub mesh: Vec<String>,
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+    #[serde(default)]
+    pub max_fuel: Option<u64>,
+}
+
+impl Capabilities {
+    pub fn declare(&mut self, capability: Capability) {
+        fn extend(list: &mut Vec<String>, values: Vec<String>) {
+            for value in values {
+                if !list.contains(&value) {
+                    list.push(value);
+                }
+            }
+        }
+
+        match capability {
+            Capability::Mount(paths) => extend(&mut self.mounts, paths),
+            Capability::Env(names) => extend(&mut self.env, names),
+            Capability::Network(rules) => extend(&mut self.network, rules),
+            Capability::Mesh(peers) => extend(&mut self.mesh, peers),
+            Capability::MaxMemoryMb(mb) => self.max_memory_mb = Some(mb),
+            Capability::MaxFuel(fuel) => self.max_fuel = Some(fuel),
+        }
+    }
+
+    /// Check what `config` grants against the manifest, fill in the declared
+    /// memory/fuel ceilings and restrict the mesh. Fails on the first grant
+    /// that was not declared.
+    pub fn enforce(&self, config: &mut RunConfig) -> Result<()> {
+        if let Some(map_dir) = &config.map_dir {
+            let guest = map_dir.split_once(':').map(|(_, guest)| guest).unwrap_or(map_dir);
+            if !self.mounts.iter().any(|m| m == guest) {
+                anyhow::bail!("Image does not declare mount '{}' (add `CAPABILITY mount {}`)", guest, guest);
+            }
+        }
+        if let Some(dir) = &config.secrets_dir {
+            if !self.mounts.iter().any(|m| m == dir) {
+                anyhow::bail!("Image does not declare mount '{}' for --secrets-dir", dir);
+            }
+        }
+
+        let names = config.env.iter().map(|(k, _)| k).chain(config.secrets.iter().map(|s| &s.name));
+        for name in names {
+            if !self.env.contains(name) {
+                anyhow::bail!("Image does not declare environment variable '{}' (add `CAPABILITY env {}`)", name, name);
+            }
+        }
+
+        let declared = self.network.iter().map(|r| HttpRule::parse(r)).collect::<Result<Vec<_>>>()?;
+        for rule in &config.http.allow {
+            if !declared.iter().any(|d| covers(d, rule)) {
+                anyhow::bail!("Image does not declare network access to {}", format_rule(rule));
+            }
+        }
+
+        if let Some(max) = self.max_memory_mb {
+            match config.memory_limit_mb {
+                Some(mb) if mb > max => anyhow::bail!("--memory {} exceeds the image's declared maximum of {} MB", mb, max),
+                Some(_) => {}
+                None => config.memory_limit_mb = Some(max),
+            }
+        }
+        if let Some(max) = self.max_fuel {
+            // u64::MAX is the "unlimited" default
+            if config.fuel == u64::MAX {
+                config.fuel = max;
+            } else if config.fuel > max {
+                anyhow::bail!("--fuel {} exceeds the image's declared maximum of {}", config.fuel, max);
+            }
+        }
+
+        config.mesh_peers = if self.mesh.iter().any(|p| p == "*") {
+            None
+        } else {
+            Some(self.mesh.iter().filter_map(|p| p.parse().ok()).collect())
+        };
+
+        Ok(())
+    }
+}
+
+/// Whether the declared rule allows everything `requested` does.
+fn covers(declared: &HttpRule, requested: &HttpRule) -> bool {
+    let host_ok = declared.host == requested.host || match declared.host.strip_prefix("*.") {
+        Some(suffix) => requested.host.ends_with(&format!(".{}", suffix))
+            || requested.host.strip_prefix("*.").is_some_and(|h| h == suffix || h.ends_with(&format!(".{}", suffix))),
+        None => false,
+    };
+    let port_ok = declared.port.is_none() || declared.port == requested.port;
+    let methods_ok = declared.methods.iter().any(|m| m == "*")
+        || requested.methods.iter().all(|m| declared.methods.contains(m));
+    host_ok && port_ok && methods_ok
+}
+
+fn format_rule(rule: &HttpRule) -> String {
+    match rule.port {
+        Some(port) => format!("{}:{} ({})", rule.host, port, rule.methods.join(",")),
+        None => format!("{} ({})", rule.host, rule.methods.join(",")),
+    }
+}
+
+/// What a container is actually granted, one capability per line.
+pub fn effective_policy(config: &RunConfig) -> String {
+    let or_none = |items: Vec<String>| if items.is_empty() { "none".to_string() } else { items.join(", ") };
+
+    let mut mounts: Vec<String> = config.map_dir.iter().cloned().collect();
+    mounts.extend(config.volumes.iter().map(|(host, guest)| format!("{} (volume {})", guest, host.display())));
+    if let Some(dir) = &config.secrets_dir {
+        mounts.push(format!("{} (secrets, read-only)", dir));
+    }
+    let mut env: Vec<String> = config.env.iter().map(|(k, _)| k.clone()).collect();
+    env.extend(config.secrets.iter().map(|s| format!("{} (secret)", s.name)));
+    let network = config.http.allow.iter().map(format_rule).collect();
+    let mesh = match &config.mesh_peers {
+        None => "all replicas".to_string(),
+        Some(peers) => or_none(peers.iter().map(|p| format!("pid {}", p)).collect()),
+    };
+    let memory = config.memory_limit_mb.map(|mb| format!("{} MB", mb)).unwrap_or_else(|| "unlimited".to_string());
+    let fuel = if config.fuel == u64::MAX { "unlimited".to_string() } else { config.fuel.to_string() };
+
+    format!(
+        "  mounts:  {}\n  env:     {}\n  network: {}\n  mesh:    {}\n  memory:  {}\n  fuel:    {}",
+        or_none(mounts), or_none(env), or_none(network), mesh, memory, fuel,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Capabilities {
+        let mut caps = Capabilities::default();
+        for line in ["mount /data", "env PORT", "network *.example.com=GET,POST", "memory 64"] {
+            caps.declare(Capability::parse(line).unwrap());
+        }
+        caps
+    }
+
+    #[test]
+    fn test_enforce_fills_ceilings() {
+        let mut config = RunConfig::new("app");
+        config.map_dir = Some("./data:/data".to_string());
+        config.env = vec![("PORT".to_string(), "8080".to_string())];
+        config.http.allow = vec![HttpRule::parse("api.example.com=POST").unwrap()];
+
+        manifest().enforce(&mut config).unwrap();
+        assert_eq!(config.memory_limit_mb, Some(64));
+        assert_eq!(config.mesh_peers, Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_enforce_rejects_undeclared() {
+        let mut config = RunConfig::new("app");
+        config.map_dir = Some("./etc:/etc".to_string());
+        assert!(manifest().enforce(&mut config).is_err());
+
+        let mut config = RunConfig::new("app");
+        config.http.allow = vec![HttpRule::parse("api.example.com=DELETE").unwrap()];
+        assert!(manifest().enforce(&mut config).is_err());
+
+        let mut config = RunConfig::new("app");
+        config.memory_limit_mb = Some(128);
+        assert!(manifest().enforce(&mut config).is_err());
+    }
+}
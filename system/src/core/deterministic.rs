@@ -0,0 +1,154 @@
+use cap_rand::rngs::StdRng;
+use cap_rand::SeedableRng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime_wasi::preview2::{HostMonotonicClock, HostWallClock};
+
+/// Virtual wall clock start: 2000-01-01T00:00:00Z.
+const EPOCH_SECS: u64 = 946_684_800;
+
+/// Virtual time advances by this much on every clock read.
+const TICK_NANOS: u64 = 1_000_000;
+
+/// Fuel consumed between cooperative yields, so replicas interleave by
+/// executed instructions instead of wall-clock time.
+pub const YIELD_INTERVAL: u64 = 10_000;
+
+/// Time source for deterministic runs: a counter that only moves when read.
+#[derive(Default)]
+pub struct VirtualTime {
+    nanos: AtomicU64,
+}
+
+impl VirtualTime {
+    /// Advance one tick and return nanoseconds since the run started.
+    pub fn tick(&self) -> u64 {
+        self.nanos.fetch_add(TICK_NANOS, Ordering::SeqCst) + TICK_NANOS
+    }
+
+    /// Advance one tick and return the virtual time since the Unix epoch.
+    pub fn wall(&self) -> Duration {
+        Duration::from_secs(EPOCH_SECS) + Duration::from_nanos(self.tick())
+    }
+}
+
+pub struct VirtualWallClock(pub Arc<VirtualTime>);
+
+impl HostWallClock for VirtualWallClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(TICK_NANOS)
+    }
+
+    fn now(&self) -> Duration {
+        self.0.wall()
+    }
+}
+
+pub struct VirtualMonotonicClock(pub Arc<VirtualTime>);
+
+impl HostMonotonicClock for VirtualMonotonicClock {
+    fn resolution(&self) -> u64 {
+        TICK_NANOS
+    }
+
+    fn now(&self) -> u64 {
+        self.0.tick()
+    }
+}
+
+/// Independent RNG streams derived from one seed (WASI `random_get`,
+/// WASI insecure random, `nova_random`).
+pub fn seeded_rng(seed: u64, stream: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+/// First of the four RNG streams instance `pid` owns: `+1` WASI secure
+/// random, `+2` WASI insecure random, `+3` `nova_random`.
+fn instance_stream(pid: u32) -> u64 {
+    pid as u64 * 4
+}
+
+/// Seeded WASI secure and insecure random for instance `pid`. Deterministic
+/// runs and recordings of them both take their generators from here.
+pub fn wasi_rngs(seed: u64, pid: u32) -> (StdRng, StdRng) {
+    let stream = instance_stream(pid);
+    (seeded_rng(seed, stream + 1), seeded_rng(seed, stream + 2))
+}
+
+/// Per-instance deterministic host state used by the `nova_*` host functions.
+pub struct Determinism {
+    pub time: Arc<VirtualTime>,
+    pub rng: StdRng,
+}
+
+impl Determinism {
+    pub fn new(seed: u64, pid: u32) -> Self {
+        Self {
+            time: Arc::new(VirtualTime::default()),
+            rng: seeded_rng(seed, instance_stream(pid) + 3),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::logging::LogRecord;
+    use crate::core::runtime::{run_wasm, RunConfig};
+    use std::sync::{Arc, Mutex};
+
+    /// Logs `nova_clock_ms`, a WASI clock read, `nova_random` and WASI
+    /// `random_get` bytes, hex-encoded as letters a..p.
+    const GUEST: &str = r#"(module
+        (import "env" "nova_clock_ms" (func $clock (result i64)))
+        (import "env" "nova_random" (func $random (param i32 i32) (result i32)))
+        (import "env" "nova_log" (func $log (param i32 i32 i32)))
+        (import "wasi_snapshot_preview1" "clock_time_get" (func $time (param i32 i64 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start") (local $i i32) (local $b i32)
+            (i64.store (i32.const 0) (call $clock))
+            (drop (call $time (i32.const 0) (i64.const 1) (i32.const 8)))
+            (drop (call $random (i32.const 16) (i32.const 16)))
+            (drop (call $random_get (i32.const 32) (i32.const 16)))
+            (loop $hex
+                (local.set $b (i32.load8_u (local.get $i)))
+                (i32.store8 (i32.add (i32.const 64) (i32.shl (local.get $i) (i32.const 1)))
+                    (i32.add (i32.const 97) (i32.shr_u (local.get $b) (i32.const 4))))
+                (i32.store8 (i32.add (i32.const 65) (i32.shl (local.get $i) (i32.const 1)))
+                    (i32.add (i32.const 97) (i32.and (local.get $b) (i32.const 15))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $hex (i32.lt_u (local.get $i) (i32.const 48))))
+            (call $log (i32.const 2) (i32.const 64) (i32.const 96))))"#;
+
+    async fn run(path: &str, seed: u64) -> String {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let mut config = RunConfig::new(format!("deterministic-test-{}", uuid::Uuid::new_v4()));
+        config.deterministic = Some(seed);
+        config.log_sink = {
+            let messages = messages.clone();
+            Arc::new(move |record: &LogRecord| messages.lock().unwrap().push(record.message.clone()))
+        };
+        run_wasm(path, config).await.unwrap();
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        messages[0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_run() {
+        let path = std::env::temp_dir().join(format!("nova_deterministic_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, GUEST).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let first = run(&path, 7).await;
+        let second = run(&path, 7).await;
+        let other = run(&path, 8).await;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(first, second);
+        // Clocks are virtual and do not depend on the seed; random bytes do
+        assert_eq!(first[..32], other[..32]);
+        assert_ne!(first[32..], other[32..]);
+    }
+}
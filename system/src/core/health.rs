@@ -0,0 +1,206 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::core::logging::LogLevel;
+
+/// What a health check asks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Probe {
+    /// A guest export `() -> i32`; 0 means healthy.
+    Export(String),
+    /// `GET http://127.0.0.1:<port><path>`; a 2xx or 3xx status means healthy.
+    Http { port: u16, path: String },
+}
+
+/// A Novafile `HEALTHCHECK`. Stored in `ImageMetadata`; `nova run` probes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Healthcheck {
+    pub probe: Probe,
+    pub interval_secs: u64,
+    /// Limit on one HTTP request. Export probes run to completion.
+    pub timeout_secs: u64,
+    /// Consecutive failures before the container counts as unhealthy.
+    pub retries: u32,
+}
+
+impl Healthcheck {
+    /// Parse `[--interval=30s] [--timeout=30s] [--retries=3] export <name>`
+    /// or `... http <port> [path]`. `NONE` gives `None`, turning off the
+    /// base image's check.
+    pub fn parse(words: &[String]) -> Result<Option<Self>> {
+        if let [none] = words {
+            if none.eq_ignore_ascii_case("none") {
+                return Ok(None);
+            }
+        }
+
+        let mut check = Healthcheck {
+            probe: Probe::Export(String::new()),
+            interval_secs: 30,
+            timeout_secs: 30,
+            retries: 3,
+        };
+        let options = words.iter().take_while(|word| word.starts_with("--")).count();
+        for option in &words[..options] {
+            match option.split_once('=') {
+                Some(("--interval", value)) => check.interval_secs = parse_duration(value)?,
+                Some(("--timeout", value)) => check.timeout_secs = parse_duration(value)?,
+                Some(("--retries", value)) => {
+                    check.retries = value.parse().ok().filter(|retries| *retries > 0)
+                        .ok_or_else(|| anyhow::anyhow!("--retries expects a positive number, got '{}'", value))?;
+                }
+                _ => anyhow::bail!("Unknown HEALTHCHECK option: {}", option),
+            }
+        }
+
+        check.probe = match &words[options..] {
+            [kind, name] if kind.eq_ignore_ascii_case("export") => Probe::Export(name.clone()),
+            [kind, port, rest @ ..] if kind.eq_ignore_ascii_case("http") && rest.len() <= 1 => {
+                let port = port.parse()
+                    .map_err(|_| anyhow::anyhow!("HEALTHCHECK http expects a port number, got '{}'", port))?;
+                let path = rest.first().cloned().unwrap_or_else(|| "/".to_string());
+                if !path.starts_with('/') {
+                    anyhow::bail!("HEALTHCHECK http path must start with '/', got '{}'", path);
+                }
+                Probe::Http { port, path }
+            }
+            _ => anyhow::bail!("HEALTHCHECK expects `export <name>`, `http <port> [path]` or NONE"),
+        };
+        Ok(Some(check))
+    }
+}
+
+/// `30s`, `5m`, `1h` or plain seconds; at least one second.
+fn parse_duration(value: &str) -> Result<u64> {
+    let (number, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => value.split_at(i),
+        None => (value, "s"),
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => anyhow::bail!("Invalid duration '{}' (expected e.g. 30s, 5m or 1h)", value),
+    };
+    number.parse::<u64>().ok()
+        .filter(|n| *n > 0)
+        .map(|n| n * scale)
+        .ok_or_else(|| anyhow::anyhow!("Invalid duration '{}' (expected e.g. 30s, 5m or 1h)", value))
+}
+
+/// Probe results of one instance, turned into log records when its health changes.
+pub struct HealthMonitor {
+    pub check: Healthcheck,
+    failures: u32,
+    healthy: Option<bool>,
+    next: Instant,
+}
+
+impl HealthMonitor {
+    /// The first probe is due one interval after start.
+    pub fn new(check: Healthcheck) -> Self {
+        let next = Instant::now() + Duration::from_secs(check.interval_secs);
+        HealthMonitor { check, failures: 0, healthy: None, next }
+    }
+
+    /// Record a probe result and schedule the next probe. Failures are
+    /// reported until `retries` in a row make the instance unhealthy; after
+    /// that only recovery is.
+    pub fn record(&mut self, result: Result<()>) -> Option<(LogLevel, String)> {
+        self.next = Instant::now() + Duration::from_secs(self.check.interval_secs);
+        match result {
+            Ok(()) => {
+                self.failures = 0;
+                let changed = self.healthy != Some(true);
+                self.healthy = Some(true);
+                changed.then(|| (LogLevel::Info, "health check passed, container is healthy".to_string()))
+            }
+            Err(e) => {
+                self.failures += 1;
+                if self.healthy == Some(false) {
+                    None
+                } else if self.failures >= self.check.retries {
+                    self.healthy = Some(false);
+                    Some((LogLevel::Error, format!("container is unhealthy after {} failed health checks: {:#}", self.failures, e)))
+                } else {
+                    Some((LogLevel::Warn, format!("health check failed ({}/{}): {:#}", self.failures, self.check.retries, e)))
+                }
+            }
+        }
+    }
+}
+
+/// Resolves when `monitor`'s next probe is due; never without a monitor.
+pub async fn due(monitor: Option<&HealthMonitor>) {
+    match monitor {
+        Some(monitor) => tokio::time::sleep_until(monitor.next).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Probe `port` and `path` every interval, passing health changes to `report`.
+/// Runs until the future is dropped.
+pub async fn probe_http(check: Healthcheck, report: impl Fn(LogLevel, String)) {
+    let Probe::Http { port, path } = &check.probe else {
+        return;
+    };
+    let url = format!("http://127.0.0.1:{}{}", port, path);
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(check.timeout_secs)).build() {
+        Ok(client) => client,
+        Err(e) => return report(LogLevel::Error, format!("health checks disabled: {}", e)),
+    };
+
+    let mut monitor = HealthMonitor::new(check.clone());
+    loop {
+        due(Some(&monitor)).await;
+        let result = match client.get(&url).send().await {
+            Ok(response) if response.status().is_success() || response.status().is_redirection() => Ok(()),
+            Ok(response) => Err(anyhow::anyhow!("GET {} returned {}", url, response.status())),
+            Err(e) => Err(anyhow::anyhow!("GET {} failed: {}", url, e)),
+        };
+        if let Some((level, message)) = monitor.record(result) {
+            report(level, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let check = Healthcheck::parse(&words("--interval=10s --retries=2 export health")).unwrap().unwrap();
+        assert_eq!(check.probe, Probe::Export("health".to_string()));
+        assert_eq!((check.interval_secs, check.timeout_secs, check.retries), (10, 30, 2));
+
+        let check = Healthcheck::parse(&words("--timeout=1m HTTP 8080 /healthz")).unwrap().unwrap();
+        assert_eq!(check.probe, Probe::Http { port: 8080, path: "/healthz".to_string() });
+        assert_eq!(check.timeout_secs, 60);
+
+        assert_eq!(Healthcheck::parse(&words("NONE")).unwrap(), None);
+        assert!(Healthcheck::parse(&words("--interval=0s export health")).is_err());
+        assert!(Healthcheck::parse(&words("http 8080 healthz")).is_err());
+        assert!(Healthcheck::parse(&words("export")).is_err());
+    }
+
+    #[test]
+    fn test_monitor_reports_changes() {
+        let check = Healthcheck::parse(&words("--retries=2 export health")).unwrap().unwrap();
+        let mut monitor = HealthMonitor::new(check);
+        let fail = || Err(anyhow::anyhow!("down"));
+
+        assert_eq!(monitor.record(Ok(())).unwrap().0, LogLevel::Info);
+        assert!(monitor.record(Ok(())).is_none());
+        assert_eq!(monitor.record(fail()).unwrap().0, LogLevel::Warn);
+        assert_eq!(monitor.record(fail()).unwrap().0, LogLevel::Error);
+        assert!(monitor.record(fail()).is_none());
+        assert_eq!(monitor.record(Ok(())).unwrap().0, LogLevel::Info);
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use std::any::Any;
+use wasmtime::Linker;
+use crate::core::runtime::{NovaState, RunConfig};
+
+/// A set of host functions guests can import, plus the per-instance state
+/// they work on. `run_wasm` links the built-in `nova_*` functions this way
+/// and then every module in `RunConfig::host_modules`.
+///
+/// Functions are defined directly on the Wasmtime linker, so async host
+/// functions use `func_wrapN_async`. They reach their state through
+/// `caller.data().state::<T>()`:
+///
+/// ```
+/// # use anyhow::Result;
+/// # use std::any::Any;
+/// # use wasmtime::{Caller, Linker};
+/// # use nova::{HostModule, NovaState, RunConfig};
+/// struct Counter;
+/// struct Count(u64);
+///
+/// impl HostModule for Counter {
+///     fn name(&self) -> &str { "counter" }
+///
+///     fn state(&self, _config: &RunConfig) -> Result<Option<Box<dyn Any + Send + Sync>>> {
+///         Ok(Some(Box::new(Count(0))))
+///     }
+///
+///     fn add_to_linker(&self, linker: &mut Linker<NovaState>) -> Result<()> {
+///         linker.func_wrap1_async("counter", "add", |mut caller: Caller<'_, NovaState>, n: u64| {
+///             Box::new(async move {
+///                 let count = caller.data_mut().state_mut::<Count>().unwrap();
+///                 count.0 += n;
+///                 count.0
+///             })
+///         })?;
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait HostModule: Send + Sync {
+    /// Short name used in error messages.
+    fn name(&self) -> &str;
+
+    /// Per-instance state, created before the guest is instantiated. Each
+    /// state type can be registered by only one module.
+    fn state(&self, _config: &RunConfig) -> Result<Option<Box<dyn Any + Send + Sync>>> {
+        Ok(None)
+    }
+
+    /// Define this module's functions. Names already defined by another
+    /// module are an error.
+    fn add_to_linker(&self, linker: &mut Linker<NovaState>) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use wasmtime::Caller;
+
+    /// Keeps the values a guest pushes and sums them on request, under the
+    /// given import module name.
+    struct Tally(&'static str);
+    struct Pushed(Vec<i32>);
+
+    impl HostModule for Tally {
+        fn name(&self) -> &str { self.0 }
+
+        fn state(&self, _config: &RunConfig) -> Result<Option<Box<dyn Any + Send + Sync>>> {
+            Ok(Some(Box::new(Pushed(Vec::new()))))
+        }
+
+        fn add_to_linker(&self, linker: &mut Linker<NovaState>) -> Result<()> {
+            linker.func_wrap(self.0, "push", |mut caller: Caller<'_, NovaState>, n: i32| {
+                caller.data_mut().state_mut::<Pushed>().unwrap().0.push(n);
+            })?;
+            linker.func_wrap(self.0, "sum", |caller: Caller<'_, NovaState>| -> i32 {
+                caller.data().state::<Pushed>().unwrap().0.iter().sum()
+            })?;
+            Ok(())
+        }
+    }
+
+    /// Tries to replace a built-in import.
+    struct Shadow;
+
+    impl HostModule for Shadow {
+        fn name(&self) -> &str { "shadow" }
+
+        fn add_to_linker(&self, linker: &mut Linker<NovaState>) -> Result<()> {
+            linker.func_wrap("env", "nova_log", |_: i32, _: i32, _: i32| {})?;
+            Ok(())
+        }
+    }
+
+    async fn run(guest: &str, host_modules: Vec<Arc<dyn HostModule>>) -> Result<()> {
+        let path = std::env::temp_dir().join(format!("nova_host_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, guest).unwrap();
+        let mut config = RunConfig::new(format!("host-test-{}", uuid::Uuid::new_v4()));
+        config.host_modules = host_modules;
+        let result = crate::core::runtime::run_wasm(&path.to_string_lossy(), config).await;
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[tokio::test]
+    async fn test_guest_calls_custom_module() {
+        // Traps unless `sum` sees the state left by both `push` calls
+        run(r#"(module
+            (import "tally" "push" (func $push (param i32)))
+            (import "tally" "sum" (func $sum (result i32)))
+            (func (export "_start")
+                (call $push (i32.const 2))
+                (call $push (i32.const 3))
+                (if (i32.ne (call $sum) (i32.const 5)) (then unreachable))))"#, vec![Arc::new(Tally("tally"))]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unused_module_is_harmless() {
+        run(r#"(module (func (export "_start")))"#, vec![Arc::new(Tally("tally"))]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_builtin_names_and_state_types_are_not_shared() {
+        let guest = r#"(module (func (export "_start")))"#;
+
+        let error = run(guest, vec![Arc::new(Shadow)]).await.unwrap_err().to_string();
+        assert!(error.contains("shadow"), "{}", error);
+
+        let error = run(guest, vec![Arc::new(Tally("tally")), Arc::new(Tally("again"))]).await.unwrap_err().to_string();
+        assert!(error.contains("already registered"), "{}", error);
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// One allow-list entry: a host (optionally with port) and the methods
+/// a guest may use against it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpRule {
+    pub host: String,
+    pub port: Option<u16>,
+    pub methods: Vec<String>,
+}
+
+impl HttpRule {
+    /// Parse `host[:port][=METHOD,METHOD]`, e.g. `api.internal=GET,POST`,
+    /// `localhost:8080` or `[::1]:8080`. Without methods only GET is allowed.
+    /// A leading `*.` matches any subdomain.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (target, methods) = match spec.split_once('=') {
+            Some((target, methods)) => (target, methods),
+            None => (spec, "GET"),
+        };
+
+        // IPv6 hosts are kept in brackets, the form URLs report them in
+        let (host, port) = if target.starts_with('[') {
+            let end = target.find(']')
+                .ok_or_else(|| anyhow::anyhow!("Unclosed '[' in HTTP rule '{}'", spec))?;
+            match &target[end + 1..] {
+                "" => (target[..=end].to_string(), None),
+                rest => match rest.strip_prefix(':') {
+                    Some(port) => (target[..=end].to_string(), Some(port)),
+                    None => anyhow::bail!("Unexpected '{}' after the host in HTTP rule '{}'", rest, spec),
+                },
+            }
+        } else if target.matches(':').count() > 1 {
+            (format!("[{}]", target), None)
+        } else {
+            match target.rsplit_once(':') {
+                Some((host, port)) => (host.to_string(), Some(port)),
+                None => (target.to_string(), None),
+            }
+        };
+        let port = port
+            .map(|port| port.parse::<u16>().map_err(|_| anyhow::anyhow!("Invalid port in HTTP rule '{}'", spec)))
+            .transpose()?;
+
+        if host.is_empty() || host == "[]" {
+            anyhow::bail!("HTTP rule '{}' has no host", spec);
+        }
+
+        let methods: Vec<String> = methods.split(',')
+            .map(|m| m.trim().to_uppercase())
+            .filter(|m| !m.is_empty())
+            .collect();
+        if methods.is_empty() {
+            anyhow::bail!("HTTP rule '{}' has no methods", spec);
+        }
+
+        Ok(HttpRule { host: host.to_lowercase(), port, methods })
+    }
+
+    fn matches(&self, method: &str, host: &str, port: Option<u16>) -> bool {
+        let host_ok = match self.host.strip_prefix("*.") {
+            Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+            None => host == self.host,
+        };
+        let port_ok = self.port.is_none() || self.port == port;
+        let method_ok = self.methods.iter().any(|m| m == "*" || m == method);
+        host_ok && port_ok && method_ok
+    }
+}
+
+/// Egress policy for `nova_http_fetch`. The default denies everything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpPolicy {
+    pub allow: Vec<HttpRule>,
+    pub timeout: Duration,
+    pub max_response_bytes: usize,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            timeout: Duration::from_secs(10),
+            max_response_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Why a fetch was refused or failed. `code()` is what the guest sees.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchError {
+    Denied,
+    Timeout,
+    TooLarge,
+    InvalidRequest,
+    Failed(String),
+}
+
+impl FetchError {
+    pub fn code(&self) -> i32 {
+        match self {
+            FetchError::Denied => -1,
+            FetchError::Timeout => -2,
+            FetchError::TooLarge => -3,
+            FetchError::InvalidRequest => -4,
+            FetchError::Failed(_) => -5,
+        }
+    }
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Denied => write!(f, "denied by policy"),
+            FetchError::Timeout => write!(f, "timed out"),
+            FetchError::TooLarge => write!(f, "response exceeds size cap"),
+            FetchError::InvalidRequest => write!(f, "invalid request"),
+            FetchError::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpPolicy {
+    /// Check a request against the allow-list.
+    pub fn check(&self, method: &str, url: &reqwest::Url) -> Result<(), FetchError> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(FetchError::InvalidRequest);
+        }
+        let host = url.host_str().ok_or(FetchError::InvalidRequest)?.to_lowercase();
+        let port = url.port_or_known_default();
+
+        if self.allow.iter().any(|rule| rule.matches(method, &host, port)) {
+            Ok(())
+        } else {
+            Err(FetchError::Denied)
+        }
+    }
+}
+
+/// `scheme://host[:port]/path` of `url`, for logs: query strings and
+/// userinfo often carry tokens.
+pub fn log_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => {
+            let host = url.host_str().unwrap_or_default();
+            match url.port() {
+                Some(port) => format!("{}://{}:{}{}", url.scheme(), host, port, url.path()),
+                None => format!("{}://{}{}", url.scheme(), host, url.path()),
+            }
+        }
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+/// Perform an outbound request on behalf of a guest, enforcing the policy,
+/// the request timeout and the response size cap. Redirects are not
+/// followed so every host contacted goes through the allow-list.
+pub async fn fetch(policy: &HttpPolicy, method: &str, url: &str, body: Vec<u8>) -> Result<HttpResponse, FetchError> {
+    let method = method.to_uppercase();
+    let url = reqwest::Url::parse(url).map_err(|_| FetchError::InvalidRequest)?;
+    policy.check(&method, &url)?;
+
+    let method = reqwest::Method::from_bytes(method.as_bytes())
+        .map_err(|_| FetchError::InvalidRequest)?;
+
+    let client = reqwest::Client::builder()
+        .timeout(policy.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| FetchError::Failed(e.to_string()))?;
+
+    let mut request = client.request(method, url);
+    if !body.is_empty() {
+        request = request.body(body);
+    }
+
+    let map_err = |e: reqwest::Error| {
+        if e.is_timeout() { FetchError::Timeout } else { FetchError::Failed(e.to_string()) }
+    };
+
+    let mut response = request.send().await.map_err(map_err)?;
+    let status = response.status().as_u16();
+
+    if let Some(len) = response.content_length() {
+        if len as usize > policy.max_response_bytes {
+            return Err(FetchError::TooLarge);
+        }
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(map_err)? {
+        if body.len() + chunk.len() > policy.max_response_bytes {
+            return Err(FetchError::TooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(HttpResponse { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+
+    async fn stand_in_server() -> String {
+        let app = Router::new()
+            .route("/hello", get(|| async { "hello from upstream" }))
+            .route("/big", get(|| async { "x".repeat(4096) }))
+            .route("/slow", get(|| async {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                "late"
+            }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("127.0.0.1:{}", addr.port())
+    }
+
+    fn policy_for(rule: &str) -> HttpPolicy {
+        HttpPolicy {
+            allow: vec![HttpRule::parse(rule).unwrap()],
+            timeout: Duration::from_millis(500),
+            max_response_bytes: 1024,
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = HttpRule::parse("localhost:8080=get,post").unwrap();
+        assert_eq!(rule.host, "localhost");
+        assert_eq!(rule.port, Some(8080));
+        assert_eq!(rule.methods, vec!["GET", "POST"]);
+
+        let rule = HttpRule::parse("api.internal").unwrap();
+        assert_eq!(rule.port, None);
+        assert_eq!(rule.methods, vec!["GET"]);
+
+        let rule = HttpRule::parse("[::1]:8080=POST").unwrap();
+        assert_eq!((rule.host.as_str(), rule.port), ("[::1]", Some(8080)));
+        assert_eq!(HttpRule::parse("::1").unwrap().host, "[::1]");
+        assert!(HttpRule::parse("[::1").is_err());
+        assert!(HttpRule::parse("[::1]8080").is_err());
+
+        let url = reqwest::Url::parse("http://[::1]:8080/hello").unwrap();
+        assert!(policy_for("[::1]:8080").check("GET", &url).is_ok());
+    }
+
+    #[test]
+    fn test_log_url_drops_credentials() {
+        assert_eq!(log_url("https://user:pw@api.example.com:8443/v1/items?token=abc#frag"), "https://api.example.com:8443/v1/items");
+        assert_eq!(log_url("not a url"), "<invalid url>");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_allowed() {
+        let host = stand_in_server().await;
+        let policy = policy_for(&host);
+
+        let resp = fetch(&policy, "GET", &format!("http://{}/hello", host), Vec::new()).await.unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, b"hello from upstream");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_denied() {
+        let host = stand_in_server().await;
+        let policy = policy_for(&host);
+
+        let err = fetch(&policy, "POST", &format!("http://{}/hello", host), Vec::new()).await.unwrap_err();
+        assert_eq!(err, FetchError::Denied);
+
+        let err = fetch(&HttpPolicy::default(), "GET", &format!("http://{}/hello", host), Vec::new()).await.unwrap_err();
+        assert_eq!(err, FetchError::Denied);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_limits() {
+        let host = stand_in_server().await;
+        let policy = policy_for(&host);
+
+        let err = fetch(&policy, "GET", &format!("http://{}/big", host), Vec::new()).await.unwrap_err();
+        assert_eq!(err, FetchError::TooLarge);
+
+        let err = fetch(&policy, "GET", &format!("http://{}/slow", host), Vec::new()).await.unwrap_err();
+        assert_eq!(err, FetchError::Timeout);
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::sync::Arc;
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, TypeRef, ValType};
+use crate::core::host::HostModule;
+use crate::core::runtime;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportInfo {
+    pub module: String,
+    pub name: String,
+    /// `func (i32, i32) -> i32`, `memory 1..16 pages`, ...
+    pub ty: String,
+    /// Why `nova run` could not provide this import, if it cannot.
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportInfo {
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryInfo {
+    pub imported: bool,
+    pub min_pages: u64,
+    pub max_pages: Option<u64>,
+    pub memory64: bool,
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomSectionInfo {
+    pub name: String,
+    pub size: usize,
+}
+
+/// What a module needs from the host and what it offers.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleInfo {
+    pub size: usize,
+    pub imports: Vec<ImportInfo>,
+    pub exports: Vec<ExportInfo>,
+    pub memories: Vec<MemoryInfo>,
+    pub custom_sections: Vec<CustomSectionInfo>,
+    /// Reasons `nova run` would fail before the guest runs.
+    pub problems: Vec<String>,
+}
+
+impl ModuleInfo {
+    /// Imports grouped by namespace, in first-seen order.
+    pub fn imports_by_module(&self) -> Vec<(&str, Vec<&ImportInfo>)> {
+        let mut groups: Vec<(&str, Vec<&ImportInfo>)> = Vec::new();
+        for import in &self.imports {
+            match groups.iter_mut().find(|(module, _)| *module == import.module) {
+                Some((_, list)) => list.push(import),
+                None => groups.push((&import.module, vec![import])),
+            }
+        }
+        groups
+    }
+}
+
+/// Validate `wasm` (binary or text) and describe its interface. Imports are
+/// checked against what `nova run` links, plus `host_modules`.
+pub fn inspect(wasm: &[u8], host_modules: &[Arc<dyn HostModule>]) -> Result<ModuleInfo> {
+    let wasm = wat::parse_bytes(wasm)?;
+    wasmparser::validate(&wasm).context("Module failed validation")?;
+
+    let mut types: Vec<Option<FuncType>> = Vec::new();
+    let mut func_types: Vec<u32> = Vec::new();
+    let mut info = ModuleInfo {
+        size: wasm.len(),
+        imports: Vec::new(),
+        exports: Vec::new(),
+        memories: Vec::new(),
+        custom_sections: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    for payload in Parser::new(0).parse_all(&wasm) {
+        match payload? {
+            Payload::Version { encoding: wasmparser::Encoding::Component, .. } => {
+                anyhow::bail!("This is a component, not a core module; nova run expects a core module");
+            }
+            Payload::TypeSection(reader) => {
+                for group in reader {
+                    for ty in group?.into_types() {
+                        types.push(match ty.composite_type {
+                            wasmparser::CompositeType::Func(f) => Some(f),
+                            _ => None,
+                        });
+                    }
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    let ty = match import.ty {
+                        TypeRef::Func(index) => {
+                            func_types.push(index);
+                            format_func(types.get(index as usize).and_then(|t| t.as_ref()))
+                        }
+                        TypeRef::Memory(memory) => {
+                            info.memories.push(memory_info(&memory, true));
+                            format!("memory {}", format_limits(memory.initial, memory.maximum))
+                        }
+                        TypeRef::Table(table) => format!("table {}", format_limits(table.initial as u64, table.maximum.map(u64::from))),
+                        TypeRef::Global(global) => format!("global {}{:?}", if global.mutable { "mut " } else { "" }, global.content_type),
+                        TypeRef::Tag(_) => "tag".to_string(),
+                    };
+                    let func = match import.ty {
+                        TypeRef::Func(index) => types.get(index as usize).and_then(|t| t.as_ref()),
+                        _ => None,
+                    };
+                    let problem = check_import(import.module, import.name, &import.ty, func, host_modules);
+                    info.imports.push(ImportInfo {
+                        module: import.module.to_string(),
+                        name: import.name.to_string(),
+                        ty,
+                        problem,
+                    });
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for index in reader {
+                    func_types.push(index?);
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    info.memories.push(memory_info(&memory?, false));
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    let ty = match export.kind {
+                        ExternalKind::Func => {
+                            let ty = func_types.get(export.index as usize)
+                                .and_then(|t| types.get(*t as usize))
+                                .and_then(|t| t.as_ref());
+                            format_func(ty)
+                        }
+                        ExternalKind::Memory => "memory".to_string(),
+                        ExternalKind::Table => "table".to_string(),
+                        ExternalKind::Global => "global".to_string(),
+                        ExternalKind::Tag => "tag".to_string(),
+                    };
+                    info.exports.push(ExportInfo { name: export.name.to_string(), ty });
+                }
+            }
+            Payload::CustomSection(reader) => {
+                info.custom_sections.push(CustomSectionInfo {
+                    name: reader.name().to_string(),
+                    size: reader.data().len(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    for import in &info.imports {
+        if let Some(problem) = &import.problem {
+            info.problems.push(format!("import {}::{} {}", import.module, import.name, problem));
+        }
+    }
+    match info.exports.iter().find(|e| e.name == "_start") {
+        None => info.problems.push("no `_start` export to run".to_string()),
+        Some(start) if start.ty != "func () -> ()" => info.problems.push(format!("`_start` has type {}, expected func () -> ()", start.ty)),
+        Some(_) => {}
+    }
+    if !info.memories.is_empty() && !info.exports.iter().any(|e| e.name == "memory") {
+        info.problems.push("memory is not exported as `memory`; nova_* host functions cannot read guest buffers".to_string());
+    }
+
+    Ok(info)
+}
+
+/// Why the `run_wasm` linker cannot satisfy this import, if it cannot. The
+/// import is linked on its own, so each one gets its own answer.
+fn check_import(module: &str, name: &str, ty: &TypeRef, func: Option<&FuncType>, host_modules: &[Arc<dyn HostModule>]) -> Option<String> {
+    let TypeRef::Func(_) = ty else {
+        return Some("is not a function; nova run provides only function imports".to_string());
+    };
+    let Some(func) = func else {
+        return Some("has an unknown type".to_string());
+    };
+
+    let list = |types: &[ValType]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+    let quote = |text: &str| text.bytes().map(|b| format!("\\{:02x}", b)).collect::<String>();
+    let probe = format!(
+        "(module (import \"{}\" \"{}\" (func (param {}) (result {}))))",
+        quote(module), quote(name), list(func.params()), list(func.results())
+    );
+    let wasm = match wat::parse_str(&probe) {
+        Ok(wasm) => wasm,
+        Err(_) => return Some(format!("has type {}, which nova inspect cannot check", format_func(Some(func)))),
+    };
+    match runtime::check_imports(&wasm, host_modules) {
+        Ok(()) => None,
+        Err(e) if e.to_string().starts_with("unknown import") => Some(match module {
+            "wasi_snapshot_preview1" => "is not a WASI preview 1 function".to_string(),
+            "env" => "is not a Nova host function".to_string(),
+            _ => format!("comes from namespace `{}`, which no host module provides", module),
+        }),
+        Err(e) => Some(format!("has the wrong signature: {}", e.root_cause())),
+    }
+}
+
+fn memory_info(memory: &wasmparser::MemoryType, imported: bool) -> MemoryInfo {
+    MemoryInfo {
+        imported,
+        min_pages: memory.initial,
+        max_pages: memory.maximum,
+        memory64: memory.memory64,
+        shared: memory.shared,
+    }
+}
+
+fn format_limits(min: u64, max: Option<u64>) -> String {
+    match max {
+        Some(max) => format!("{}..{} pages", min, max),
+        None => format!("{}.. pages", min),
+    }
+}
+
+fn format_func(ty: Option<&FuncType>) -> String {
+    match ty {
+        Some(ty) => format_sig(ty.params(), ty.results()),
+        None => "func <unknown type>".to_string(),
+    }
+}
+
+fn format_sig(params: &[ValType], results: &[ValType]) -> String {
+    let list = |types: &[ValType]| types.iter().map(|t| format!("{}", t)).collect::<Vec<_>>().join(", ");
+    format!("func ({}) -> ({})", list(params), list(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_flags_unsatisfied_imports() {
+        let info = inspect(br#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+              (import "wasi_snapshot_preview1" "fd_close" (func (param i64) (result i32)))
+              (import "env" "nova_get_pid" (func (result i32)))
+              (import "env" "nova_recv" (func (param i32) (result i32)))
+              (import "env" "emscripten_notify" (func))
+              (memory (export "memory") 1 4)
+              (func (export "_start"))
+              (@custom "producers" "x"))
+        "#, &[]).unwrap();
+
+        let groups = info.imports_by_module();
+        assert_eq!(groups[0].0, "wasi_snapshot_preview1");
+        assert_eq!(groups[1].1.len(), 3);
+        assert_eq!(info.problems.len(), 3, "{:?}", info.problems);
+        assert!(info.problems[0].contains("fd_close"));
+        assert!(info.problems[1].contains("nova_recv"));
+        assert!(info.problems[2].contains("emscripten_notify"));
+        assert_eq!(info.memories[0].max_pages, Some(4));
+        assert_eq!(info.custom_sections[0].name, "producers");
+    }
+
+    #[test]
+    fn test_inspect_knows_host_modules() {
+        struct Metrics;
+
+        impl HostModule for Metrics {
+            fn name(&self) -> &str { "metrics" }
+
+            fn add_to_linker(&self, linker: &mut wasmtime::Linker<runtime::NovaState>) -> Result<()> {
+                linker.func_wrap("metrics", "count", |_: u64| {})?;
+                Ok(())
+            }
+        }
+
+        let module = br#"(module (import "metrics" "count" (func (param i64))) (func (export "_start")))"#;
+        assert_eq!(inspect(module, &[]).unwrap().problems.len(), 1);
+        assert!(inspect(module, &[Arc::new(Metrics)]).unwrap().problems.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde::Serialize;
+
+/// Severity of a guest log record, as passed to `nova_log`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Map the numeric level used by guests (0 = trace ... 4 = error).
+    /// Unknown values are clamped to `Error` so nothing gets silently dropped.
+    pub fn from_guest(level: u32) -> Self {
+        match level {
+            0 => LogLevel::Trace,
+            1 => LogLevel::Debug,
+            2 => LogLevel::Info,
+            3 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }
+    }
+
+    /// Name stored in the dashboard `logs.level` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// A structured log record emitted by a guest.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    pub timestamp: String,
+    pub level: LogLevel,
+    pub pid: u32,
+    pub container: String,
+    pub project: Option<String>,
+    pub deployment: Option<String>,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Single-line rendering used by the CLI and `~/.nova/logs/<container>.log`.
+    pub fn format_line(&self) -> String {
+        format!("[{}] [{:<5}] [{}#{}] {}", self.timestamp, self.level.as_str(), self.container, self.pid, self.message)
+    }
+}
+
+/// Destination for guest log records. The CLI prints and appends to the
+/// container log file; the dashboard routes records into its database.
+pub type LogSink = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+/// `~/.nova/logs/<container>.log`, with `:` and `/` in the name replaced the
+/// way image tags are, so a container name cannot point outside the log dir.
+pub fn log_path(container: &str) -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(format!("{}/.nova/logs/{}.log", home, container.replace(':', "_").replace('/', "-")))
+}
+
+/// Print records to stderr and append them to `~/.nova/logs/<container>.log`,
+/// which is where `nova logs <container>` reads from.
+pub fn default_sink() -> LogSink {
+    Arc::new(|record: &LogRecord| {
+        let line = record.format_line();
+        eprintln!("{}", line);
+
+        let log_path = log_path(&record.container);
+        if log_path.parent().is_none_or(|dir| std::fs::create_dir_all(dir).is_err()) {
+            return;
+        }
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(log_path) {
+            let _ = writeln!(file, "{}", line);
+        }
+    })
+}
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use serde::Serialize;
+
+/// Resource usage of one instance, emitted periodically and when it exits.
+/// Fuel is Wasmtime's instruction count, so it doubles as a CPU measure.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub pid: u32,
+    pub container: String,
+    pub project: Option<String>,
+    pub deployment: Option<String>,
+    pub fuel_consumed: u64,
+    pub elapsed_ms: u64,
+    pub instructions_per_sec: u64,
+    pub peak_memory_bytes: u64,
+    /// False for periodic reports of a still running instance.
+    pub finished: bool,
+}
+
+impl RunReport {
+    pub fn format_line(&self) -> String {
+        format!(
+            "[{}#{}] fuel {} | {} instr/s | peak memory {:.1} MiB | {} ms{}",
+            self.container,
+            self.pid,
+            self.fuel_consumed,
+            self.instructions_per_sec,
+            self.peak_memory_bytes as f64 / (1024.0 * 1024.0),
+            self.elapsed_ms,
+            if self.finished { "" } else { " (running)" },
+        )
+    }
+}
+
+/// Destination for run reports. The CLI prints them; the dashboard stores
+/// them per deployment.
+pub type MetricsSink = Arc<dyn Fn(&RunReport) + Send + Sync>;
+
+pub fn default_sink() -> MetricsSink {
+    Arc::new(|report: &RunReport| eprintln!("📊 {}", report.format_line()))
+}
+
+/// Tracks elapsed time, fuel and peak linear memory for an instance. The
+/// store holds one handle as its resource limiter, refusing growth past
+/// `max_memory`; a clone lets a host timer report while the guest is
+/// blocked in a host call.
+#[derive(Clone)]
+pub struct Usage(Arc<Counters>);
+
+struct Counters {
+    started: Instant,
+    initial_fuel: u64,
+    /// Fuel left when the runtime last looked; fuel only moves while guest
+    /// code runs, and the epoch callback updates it then.
+    remaining_fuel: AtomicU64,
+    peak_memory: AtomicU64,
+    max_memory: Option<u64>,
+}
+
+impl Usage {
+    pub fn new(initial_fuel: u64, max_memory: Option<u64>) -> Self {
+        Usage(Arc::new(Counters {
+            started: Instant::now(),
+            initial_fuel,
+            remaining_fuel: AtomicU64::new(initial_fuel),
+            peak_memory: AtomicU64::new(0),
+            max_memory,
+        }))
+    }
+
+    /// Record the fuel left in the store.
+    pub fn observe_fuel(&self, remaining_fuel: u64) {
+        self.0.remaining_fuel.store(remaining_fuel, Ordering::Relaxed);
+    }
+
+    /// Fill in the usage part of a report from the last observed fuel.
+    pub fn report(&self, mut report: RunReport) -> RunReport {
+        let elapsed = self.0.started.elapsed();
+        report.fuel_consumed = self.0.initial_fuel.saturating_sub(self.0.remaining_fuel.load(Ordering::Relaxed));
+        report.elapsed_ms = elapsed.as_millis() as u64;
+        report.instructions_per_sec = match elapsed.as_secs_f64() {
+            secs if secs > 0.0 => (report.fuel_consumed as f64 / secs) as u64,
+            _ => 0,
+        };
+        report.peak_memory_bytes = self.0.peak_memory.load(Ordering::Relaxed);
+        report
+    }
+}
+
+impl wasmtime::ResourceLimiter for Usage {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> anyhow::Result<bool> {
+        if self.0.max_memory.is_some_and(|max| desired as u64 > max) {
+            // memory.grow returns -1 to the guest
+            return Ok(false);
+        }
+        self.0.peak_memory.fetch_max(desired as u64, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+}
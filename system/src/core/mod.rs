@@ -1,6 +1,40 @@
+//! The container engine: everything `nova` does besides the CLI and dashboard.
+
+/// Wasm execution: `RunConfig`, `run_wasm` and the `nova_*` host functions.
 pub mod runtime;
+/// Runs replicas of a module side by side.
 pub mod scheduler;
+/// In-process message switch behind `nova_send` / `nova_recv`.
 pub mod network;
+/// Novafile parsing, image building and the local image store.
 pub mod builder;
+/// Decides how a path is run (Wasm, Python, Node.js).
 pub mod runtime_detector;
+/// Native Python/Node.js runtimes and the static file server.
 pub mod runtimes;
+/// Structured guest logs.
+pub mod logging;
+/// Egress policy and client for `nova_http_fetch`.
+pub mod http;
+/// Encrypted local secret store.
+pub mod secrets;
+/// Virtual clocks and seeded randomness for `--deterministic`.
+pub mod deterministic;
+/// Host-call recording and replay.
+pub mod trace;
+/// Instance checkpoints.
+pub mod snapshot;
+/// Build-time pre-initialization of modules.
+pub mod preinit;
+/// Fuel, throughput and memory reports.
+pub mod metrics;
+/// Image capability manifests.
+pub mod capabilities;
+/// Module validation and interface listing.
+pub mod inspect;
+/// Extension point for custom host functions.
+pub mod host;
+/// `.novaignore` matching shared by builds and deploys.
+pub mod novaignore;
+/// `HEALTHCHECK` probes run by `nova run`.
+pub mod health;
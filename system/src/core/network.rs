@@ -7,6 +7,7 @@ pub type Message = Vec<u8>;
 
 /// A simple virtual switch that routes messages between containers.
 /// Process ID (pid) is a simple u32.
+#[derive(Default)]
 pub struct VirtualSwitch {
     // Map of PID -> Sender
     routes: Arc<Mutex<HashMap<u32, mpsc::Sender<Message>>>>,
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Name of the ignore file at the root of a build context or deploy directory.
+pub const FILE_NAME: &str = ".novaignore";
+
+/// Files excluded from a build context or deployment by `.novaignore`.
+/// Patterns use gitignore syntax, including `!` to re-include a path.
+pub struct NovaIgnore {
+    root: PathBuf,
+    matcher: Gitignore,
+}
+
+impl NovaIgnore {
+    /// Read `<root>/.novaignore`. A missing file excludes nothing.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(FILE_NAME);
+        let content = if path.is_file() {
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+        Self::new(root, &content)
+            .with_context(|| format!("Invalid pattern in {}", path.display()))
+    }
+
+    /// Patterns given as the contents of an ignore file.
+    pub fn new(root: &Path, content: &str) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for line in content.lines() {
+            builder.add_line(None, line)?;
+        }
+        Ok(NovaIgnore { root: root.to_path_buf(), matcher: builder.build()? })
+    }
+
+    /// Whether `path` (under the root) is excluded. As in git, nothing inside
+    /// an excluded directory can be re-included.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let mut current = PathBuf::new();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            current.push(component);
+            let last = components.peek().is_none();
+            if self.matcher.matched(&current, !last || is_dir).is_ignore() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Walk `dir` in file name order, skipping excluded entries. Symlinks are
+    /// matched by their own path; with `follow_links` they are walked as what
+    /// they point to.
+    pub fn walk<'a>(&'a self, dir: &Path, follow_links: bool) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+        WalkDir::new(dir)
+            .sort_by_file_name()
+            .follow_links(follow_links)
+            .into_iter()
+            .filter_entry(move |entry| !self.is_ignored(entry.path(), entry.file_type().is_dir()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negation_and_directories() {
+        let root = Path::new("ctx");
+        let ignore = NovaIgnore::new(root, "# comment\n*.log\n!keep.log\ntarget/\n/secret.txt\n").unwrap();
+
+        assert!(ignore.is_ignored(&root.join("debug.log"), false));
+        assert!(ignore.is_ignored(&root.join("logs/app.log"), false));
+        assert!(!ignore.is_ignored(&root.join("keep.log"), false));
+        assert!(ignore.is_ignored(&root.join("target"), true));
+        assert!(ignore.is_ignored(&root.join("target/app.wasm"), false));
+        assert!(!ignore.is_ignored(&root.join("target"), false));
+        assert!(ignore.is_ignored(&root.join("secret.txt"), false));
+        assert!(!ignore.is_ignored(&root.join("app/secret.txt"), false));
+        assert!(!ignore.is_ignored(Path::new("elsewhere/debug.log"), false));
+    }
+}
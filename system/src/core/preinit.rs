@@ -0,0 +1,316 @@
+use anyhow::{Context, Result};
+use wasm_encoder::{ConstExpr, DataCountSection, DataSection, ExportSection, GlobalSection, GlobalType, MemorySection, MemoryType, RawSection};
+use wasmparser::{DataKind, Parser, Payload, TypeRef};
+use wasmtime::{Config, Engine, Linker, Module, Store, Val};
+use crate::core::snapshot::{self, PAGE_SIZE};
+
+/// Upper bound on the work an init export may do at build time.
+const INIT_FUEL: u64 = 10_000_000_000;
+
+/// Zero runs shorter than this stay inside a data segment instead of splitting it.
+const MAX_ZERO_GAP: usize = 64;
+
+/// Data segments written across all memories, well under the 100,000 that
+/// validators accept. Larger gaps are merged when a sparse heap needs more.
+const MAX_DATA_SEGMENTS: usize = 10_000;
+
+/// Instantiate `wasm`, run its `init` export and return a module whose
+/// globals, memory sizes and data segments hold the state `init` left
+/// behind. The start function and the `init` export are dropped since their
+/// effects are already baked in. Imported functions trap if `init` calls them.
+/// Table contents are not captured, so modules that can write tables are refused.
+pub fn preinit(wasm: &[u8], init: &str) -> Result<Vec<u8>> {
+    let wasm = wat::parse_bytes(wasm)?;
+    let memory64 = check_supported(&wasm)?;
+    let (instrumented, layout) = snapshot::instrument(&wasm)?;
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, &instrumented)?;
+
+    let mut linker = Linker::new(&engine);
+    linker.define_unknown_imports_as_traps(&module)?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(INIT_FUEL)?;
+
+    let instance = linker.instantiate(&mut store, &module)
+        .context("Failed to instantiate module for pre-initialization")?;
+    let init_func = instance.get_typed_func::<(), ()>(&mut store, init)
+        .with_context(|| format!("Module has no export '{}' of type () -> ()", init))?;
+    init_func.call(&mut store, ())
+        .with_context(|| format!("Init export '{}' failed (host imports are not available at build time)", init))?;
+
+    let mut globals = Vec::new();
+    for i in 0..layout.globals {
+        let global = instance.get_global(&mut store, &snapshot::global_export(i))
+            .ok_or_else(|| anyhow::anyhow!("Global {} is not exported", i))?;
+        globals.push(global.get(&mut store));
+    }
+
+    let mut memories = Vec::new();
+    for i in 0..layout.memories {
+        let memory = instance.get_memory(&mut store, &snapshot::memory_export(i))
+            .ok_or_else(|| anyhow::anyhow!("Memory {} is not exported", i))?;
+        memories.push(memory.data(&store).to_vec());
+    }
+
+    let output = rewrite(&wasm, init, &globals, &memories, &memory64)?;
+    wasmparser::validate(&output).context("Pre-initialized module failed validation")?;
+    Ok(output)
+}
+
+/// Reject modules whose state cannot be captured by rewriting globals and
+/// data segments. Returns whether each memory is 64-bit.
+fn check_supported(wasm: &[u8]) -> Result<Vec<bool>> {
+    let mut memory64 = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    if !matches!(import.ty, TypeRef::Func(_)) {
+                        anyhow::bail!(
+                            "Cannot pre-initialize a module that imports '{}::{}'; only function imports are supported",
+                            import.module, import.name
+                        );
+                    }
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    memory64.push(memory?.memory64);
+                }
+            }
+            Payload::DataSection(reader) => {
+                for data in reader {
+                    if matches!(data?.kind, DataKind::Passive) {
+                        anyhow::bail!("Cannot pre-initialize a module with passive data segments");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if snapshot::writes_tables(wasm)? {
+        anyhow::bail!("Cannot pre-initialize a module that writes tables (table.set, table.grow, ...); table entries are not captured");
+    }
+    Ok(memory64)
+}
+
+fn const_expr(value: &Val) -> Result<ConstExpr> {
+    Ok(match value {
+        Val::I32(v) => ConstExpr::i32_const(*v),
+        Val::I64(v) => ConstExpr::i64_const(*v),
+        Val::F32(v) => ConstExpr::f32_const(f32::from_bits(*v)),
+        Val::F64(v) => ConstExpr::f64_const(f64::from_bits(*v)),
+        _ => anyhow::bail!("Cannot pre-initialize reference or vector globals"),
+    })
+}
+
+fn val_type(ty: wasmparser::ValType) -> Result<wasm_encoder::ValType> {
+    Ok(match ty {
+        wasmparser::ValType::I32 => wasm_encoder::ValType::I32,
+        wasmparser::ValType::I64 => wasm_encoder::ValType::I64,
+        wasmparser::ValType::F32 => wasm_encoder::ValType::F32,
+        wasmparser::ValType::F64 => wasm_encoder::ValType::F64,
+        _ => anyhow::bail!("Cannot pre-initialize reference or vector globals"),
+    })
+}
+
+/// Non-zero runs of a memory image as `(offset, bytes)`, at most `max` of
+/// them: if there are more, the smallest zero gaps between runs are merged.
+fn segments(memory: &[u8], max: usize) -> Vec<(usize, &[u8])> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < memory.len() {
+        if memory[i] == 0 {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut end = i + 1;
+        let mut j = end;
+        while j < memory.len() && j - end < MAX_ZERO_GAP {
+            if memory[j] != 0 {
+                end = j + 1;
+            }
+            j += 1;
+        }
+        runs.push((start, end));
+        i = j;
+    }
+
+    if runs.len() > max.max(1) {
+        let mut gaps: Vec<usize> = runs.windows(2).map(|w| w[1].0 - w[0].1).collect();
+        gaps.sort_unstable();
+        // Closing every gap up to this size leaves at most `max` runs
+        let widest = gaps[runs.len() - max.max(1) - 1];
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in runs {
+            match merged.last_mut() {
+                Some(last) if start - last.1 <= widest => last.1 = end,
+                _ => merged.push((start, end)),
+            }
+        }
+        runs = merged;
+    }
+
+    runs.into_iter().map(|(start, end)| (start, &memory[start..end])).collect()
+}
+
+/// Copy `wasm`, replacing global initializers, memory minimums and data
+/// segments with the captured state.
+fn rewrite(wasm: &[u8], init: &str, globals: &[Val], memories: &[Vec<u8>], memory64: &[bool]) -> Result<Vec<u8>> {
+    let mut data = DataSection::new();
+    let per_memory = MAX_DATA_SEGMENTS / memories.len().max(1);
+    for (index, (memory, is64)) in memories.iter().zip(memory64).enumerate() {
+        for (offset, bytes) in segments(memory, per_memory) {
+            let offset = if *is64 { ConstExpr::i64_const(offset as i64) } else { ConstExpr::i32_const(offset as i32) };
+            data.active(index as u32, &offset, bytes.iter().copied());
+        }
+    }
+
+    let mut module = wasm_encoder::Module::new();
+    let mut data_written = false;
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        match &payload {
+            Payload::GlobalSection(reader) => {
+                let mut section = GlobalSection::new();
+                for (global, value) in reader.clone().into_iter().zip(globals) {
+                    let ty = global?.ty;
+                    section.global(GlobalType { val_type: val_type(ty.content_type)?, mutable: ty.mutable }, &const_expr(value)?);
+                }
+                module.section(&section);
+                continue;
+            }
+            Payload::MemorySection(reader) => {
+                let mut section = MemorySection::new();
+                for (memory, bytes) in reader.clone().into_iter().zip(memories) {
+                    let ty = memory?;
+                    section.memory(MemoryType {
+                        minimum: (bytes.len() / PAGE_SIZE) as u64,
+                        maximum: ty.maximum,
+                        memory64: ty.memory64,
+                        shared: ty.shared,
+                    });
+                }
+                module.section(&section);
+                continue;
+            }
+            Payload::ExportSection(reader) => {
+                let mut section = ExportSection::new();
+                for export in reader.clone() {
+                    let export = export?;
+                    if export.name != init {
+                        section.export(export.name, export_kind(export.kind), export.index);
+                    }
+                }
+                module.section(&section);
+                continue;
+            }
+            // The start function already ran during pre-initialization
+            Payload::StartSection { .. } => continue,
+            Payload::DataCountSection { .. } => {
+                module.section(&DataCountSection { count: data.len() });
+                continue;
+            }
+            Payload::DataSection(_) => {
+                module.section(&data);
+                data_written = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            module.section(&RawSection { id, data: &wasm[range] });
+        }
+    }
+
+    if !data_written && !data.is_empty() {
+        module.section(&data);
+    }
+    Ok(module.finish())
+}
+
+fn export_kind(kind: wasmparser::ExternalKind) -> wasm_encoder::ExportKind {
+    match kind {
+        wasmparser::ExternalKind::Func => wasm_encoder::ExportKind::Func,
+        wasmparser::ExternalKind::Table => wasm_encoder::ExportKind::Table,
+        wasmparser::ExternalKind::Memory => wasm_encoder::ExportKind::Memory,
+        wasmparser::ExternalKind::Global => wasm_encoder::ExportKind::Global,
+        wasmparser::ExternalKind::Tag => wasm_encoder::ExportKind::Tag,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_merge_small_gaps() {
+        let mut memory = vec![0u8; 1024];
+        memory[10] = 1;
+        memory[20] = 2;
+        memory[500] = 3;
+
+        let runs = segments(&memory, MAX_DATA_SEGMENTS);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0, 10);
+        assert_eq!(runs[0].1.len(), 11);
+        assert_eq!(runs[1], (500, &[3u8][..]));
+
+        // A sparse heap is merged down to the cap, widest gaps kept open
+        memory[900] = 4;
+        let runs = segments(&memory, 2);
+        assert_eq!(runs.iter().map(|(offset, bytes)| (*offset, bytes.len())).collect::<Vec<_>>(), vec![(10, 11), (500, 401)]);
+        let sparse: Vec<u8> = (0..1_000_000).map(|i| (i % 100 == 0) as u8).collect();
+        assert!(segments(&sparse, MAX_DATA_SEGMENTS).len() <= MAX_DATA_SEGMENTS);
+    }
+
+    #[test]
+    fn test_preinit_refuses_table_writes() {
+        let wasm = wat::parse_str(r#"
+            (module
+              (table 1 funcref)
+              (func $f)
+              (elem declare func $f)
+              (func (export "init") (table.set (i32.const 0) (ref.func $f))))
+        "#).unwrap();
+        assert!(preinit(&wasm, "init").unwrap_err().to_string().contains("tables"));
+    }
+
+    #[test]
+    fn test_preinit_bakes_state() {
+        let wasm = wat::parse_str(r#"
+            (module
+              (memory 1)
+              (global $ready (mut i32) (i32.const 0))
+              (func (export "init")
+                (i32.store (i32.const 1024) (i32.const 42))
+                (global.set $ready (i32.const 1)))
+              (func (export "_start")))
+        "#).unwrap();
+
+        let output = preinit(&wasm, "init").unwrap();
+
+        let mut exports = Vec::new();
+        let mut segments = Vec::new();
+        for payload in Parser::new(0).parse_all(&output) {
+            match payload.unwrap() {
+                Payload::ExportSection(reader) => {
+                    exports.extend(reader.into_iter().map(|e| e.unwrap().name.to_string()));
+                }
+                Payload::DataSection(reader) => {
+                    segments.extend(reader.into_iter().map(|d| d.unwrap().data.to_vec()));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(exports, vec!["_start"]);
+        assert_eq!(segments, vec![vec![42u8]]);
+    }
+}
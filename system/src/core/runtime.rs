@@ -1,16 +1,274 @@
-use wasmtime::{Engine, Linker, Module, Store, Config, Instance, Caller};
-use wasmtime_wasi::preview2::{WasiCtx, Table, WasiCtxBuilder, WasiView};
+use wasmtime::{Engine, Linker, Module, Store, Config, Instance, Caller, Mutability, Val, ValType, GuestProfiler, UpdateDeadline, WasmBacktrace, WasmBacktraceDetails};
+use wasmtime_wasi::preview2::{WasiCtx, Table, WasiCtxBuilder, WasiView, HostMonotonicClock, HostWallClock};
 use wasmtime_wasi::preview2::preview1::{self, WasiPreview1View};
 use anyhow::Result;
 use tokio::sync::mpsc;
 use crate::core::network::{self, Message};
+use crate::core::runtime_detector::WASM_MAGIC;
+use crate::core::logging::{self, LogLevel, LogRecord, LogSink};
+use crate::core::http::{self, HttpPolicy};
+use crate::core::secrets::SecretValue;
+use crate::core::deterministic::{self, Determinism, VirtualMonotonicClock, VirtualWallClock};
+use crate::core::metrics::{self, MetricsSink, RunReport, Usage};
+use crate::core::snapshot::{self, Checkpointed, ControlDir, GlobalValue, Snapshot, SnapshotHeader, StateLayout};
+use crate::core::trace::{self, TraceEvent, TraceHandle, TracedMonotonicClock, TracedRng, TracedStdout, TracedWallClock};
+use cap_rand::rngs::StdRng;
+use cap_rand::{RngCore, SeedableRng};
+use crate::core::host::HostModule;
+use crate::core::health::{self, Healthcheck, HealthMonitor, Probe};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-struct NovaState {
+/// Sampling interval for `--profile`, and how often fuel is read for
+/// periodic reports; one epoch tick each.
+const PROFILE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Clocks and random sources a traced run reads through.
+type TracedSources = (Box<dyn HostWallClock>, Box<dyn HostMonotonicClock>, Box<dyn RngCore + Send + Sync>, Box<dyn RngCore + Send + Sync>);
+
+/// Largest buffer one `nova_random` call fills.
+const MAX_RANDOM_BYTES: usize = 64 * 1024;
+
+/// Per-instance options for `run_wasm`. Start from `RunConfig::new` and
+/// set fields; new fields always get a default there, so embedders keep
+/// compiling as options are added.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct RunConfig {
+    /// Replica id on the mesh, returned by `nova_get_pid`.
+    pub pid: u32,
+    /// Fuel (instructions) the guest may consume; `u64::MAX` is unlimited.
+    pub fuel: u64,
+    /// Cap on linear memory growth.
+    pub memory_limit_mb: Option<u64>,
+    /// Map host directory: host_path:guest_path
+    pub map_dir: Option<String>,
+    /// Persistent host directories and the guest paths they back (the image's VOLUMEs).
+    pub volumes: Vec<(PathBuf, String)>,
+    /// Container name used to tag guest log records.
+    pub container: String,
+    /// Dashboard project and deployment the instance belongs to, if any.
+    pub project: Option<String>,
+    pub deployment: Option<String>,
+    /// Where records written through `nova_log` end up.
+    pub log_sink: LogSink,
+    /// Hosts and methods reachable through `nova_http_fetch`.
+    pub http: HttpPolicy,
+    /// Guest argv (after the module name) and environment.
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Secrets resolved at start. Injected as environment variables, or as
+    /// read-only files under `secrets_dir` when that guest path is set.
+    pub secrets: Vec<SecretValue>,
+    pub secrets_dir: Option<String>,
+    /// Seed for deterministic mode: virtual clocks, seeded randomness,
+    /// canonical NaNs and in-order message delivery.
+    pub deterministic: Option<u64>,
+    /// Record host-boundary events to, or replay them from, a trace file.
+    pub trace: Option<TraceHandle>,
+    /// Resume from a checkpoint (calling the guest's `nova_resume`) instead of `_start`.
+    pub restore: Option<Arc<Snapshot>>,
+    /// Accept `nova checkpoint` requests. The module must import `nova_recv`,
+    /// and the instance claims `~/.nova/run/<container>/<pid>` while it runs.
+    pub checkpoint: bool,
+    /// Write a sampled guest profile (Firefox profiler format) here.
+    pub profile: Option<PathBuf>,
+    /// Emit `/tmp/perf-<pid>.map` so `perf` can symbolize JIT code.
+    pub perfmap: bool,
+    /// Where fuel/throughput/memory reports go, at exit and every `metrics_interval`.
+    pub metrics_sink: MetricsSink,
+    pub metrics_interval: Option<Duration>,
+    /// Keep DWARF, emit native unwind info and register JIT code with
+    /// gdb/lldb; traps print source-level backtraces.
+    pub debug: bool,
+    /// Peers `nova_send` may reach; `None` allows every replica.
+    pub mesh_peers: Option<Vec<u32>>,
+    /// Host functions linked after the built-in `nova_*` ones.
+    pub host_modules: Vec<Arc<dyn HostModule>>,
+    /// Probed while the instance runs; changes of health are logged.
+    pub healthcheck: Option<Healthcheck>,
+}
+
+impl RunConfig {
+    /// Defaults: pid 0, unlimited fuel and memory, no mounts, no egress,
+    /// logs and usage reports printed to the terminal.
+    pub fn new(container: impl Into<String>) -> Self {
+        Self {
+            pid: 0,
+            fuel: u64::MAX,
+            memory_limit_mb: None,
+            map_dir: None,
+            volumes: Vec::new(),
+            container: container.into(),
+            project: None,
+            deployment: None,
+            log_sink: logging::default_sink(),
+            http: HttpPolicy::default(),
+            args: Vec::new(),
+            env: Vec::new(),
+            secrets: Vec::new(),
+            secrets_dir: None,
+            deterministic: None,
+            trace: None,
+            restore: None,
+            checkpoint: false,
+            profile: None,
+            perfmap: false,
+            metrics_sink: metrics::default_sink(),
+            metrics_interval: None,
+            debug: false,
+            mesh_peers: None,
+            host_modules: Vec::new(),
+            healthcheck: None,
+        }
+    }
+}
+
+/// Store data of a running instance. Host modules reach their own state and
+/// the instance's configuration through it.
+pub struct NovaState {
     table: Table,
     ctx: WasiCtx,
     adapter: preview1::WasiPreview1Adapter,
-    pid: u32,
+    config: RunConfig,
     inbox: mpsc::Receiver<Message>,
+    determinism: Option<Determinism>,
+    module: String,
+    layout: StateLayout,
+    control: Option<ControlDir>,
+    profiler: Option<GuestProfiler>,
+    usage: Usage,
+    /// Export health checks, run while the guest waits in `nova_recv`.
+    health: Option<HealthMonitor>,
+    /// State registered by host modules, keyed by type.
+    extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl NovaState {
+    pub fn config(&self) -> &RunConfig {
+        &self.config
+    }
+
+    /// State a host module registered through `HostModule::state`.
+    pub fn state<T: Any>(&self) -> Option<&T> {
+        self.extensions.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn state_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Secret values never reach the log store or a trace file.
+    fn redact(&self, mut message: String) -> String {
+        for secret in &self.config.secrets {
+            if !secret.value.is_empty() {
+                message = message.replace(&secret.value, "***");
+            }
+        }
+        message
+    }
+
+    /// Write a log record tagged with this instance, with secrets masked.
+    pub fn log(&self, level: LogLevel, message: String) {
+        let message = self.redact(message);
+        let record = LogRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level,
+            pid: self.config.pid,
+            container: self.config.container.clone(),
+            project: self.config.project.clone(),
+            deployment: self.config.deployment.clone(),
+            message,
+        };
+        (self.config.log_sink)(&record);
+    }
+
+    fn run_report(&self, finished: bool) -> RunReport {
+        self.usage.report(RunReport {
+            pid: self.config.pid,
+            container: self.config.container.clone(),
+            project: self.config.project.clone(),
+            deployment: self.config.deployment.clone(),
+            fuel_consumed: 0,
+            elapsed_ms: 0,
+            instructions_per_sec: 0,
+            peak_memory_bytes: 0,
+            finished,
+        })
+    }
+
+    fn replaying(&self) -> bool {
+        self.config.trace.as_ref().is_some_and(|t| t.is_replay())
+    }
+
+    fn trace_input(&self, live: TraceEvent) -> TraceEvent {
+        match &self.config.trace {
+            Some(trace) => trace.input(live),
+            None => live,
+        }
+    }
+
+    fn trace_output(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &self.config.trace {
+            trace.output(event());
+        }
+    }
+}
+
+/// Host directory backing the read-only secrets mount; removed when the run ends.
+struct SecretFiles {
+    dir: std::path::PathBuf,
+}
+
+impl SecretFiles {
+    fn create(secrets: &[SecretValue]) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("nova-secrets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let files = SecretFiles { dir };
+
+        for secret in secrets {
+            let path = files.dir.join(&secret.name);
+            std::fs::write(&path, &secret.value)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o400))?;
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl Drop for SecretFiles {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The guest's exported `memory`, if it has one.
+pub fn guest_memory(caller: &mut Caller<'_, NovaState>) -> Option<wasmtime::Memory> {
+    match caller.get_export("memory") {
+        Some(wasmtime::Extern::Memory(m)) => Some(m),
+        _ => None,
+    }
+}
+
+/// Copy `len` bytes out of guest memory; `None` if out of bounds.
+pub fn read_guest(caller: &mut Caller<'_, NovaState>, ptr: u32, len: u32) -> Option<Vec<u8>> {
+    let mem = guest_memory(caller)?;
+    let start = ptr as usize;
+    mem.data(&*caller).get(start..start.checked_add(len as usize)?).map(<[u8]>::to_vec)
+}
+
+/// Copy `data` into guest memory at `ptr`; false if out of bounds.
+pub fn write_guest(caller: &mut Caller<'_, NovaState>, ptr: u32, data: &[u8]) -> bool {
+    match guest_memory(caller) {
+        Some(mem) => mem.write(&mut *caller, ptr as usize, data).is_ok(),
+        None => false,
+    }
 }
 
 impl WasiView for NovaState {
@@ -25,78 +283,306 @@ impl WasiPreview1View for NovaState {
     fn adapter_mut(&mut self) -> &mut preview1::WasiPreview1Adapter { &mut self.adapter }
 }
 
-pub async fn run_wasm(path: &str, pid: u32, fuel: u64, memory_limit_mb: Option<u64>, map_dir: Option<String>) -> Result<()> {
-    // 1. Configure Wasmtime
-    let mut config = Config::new();
-    config.async_support(true);
-    config.consume_fuel(true); // Enable fuel metering
-    config.cranelift_opt_level(wasmtime::OptLevel::Speed);
+/// The built-in `env` functions: messaging, logging, clock, randomness and HTTP.
+struct NovaHost;
 
-    if let Some(mb) = memory_limit_mb {
-        let bytes = mb * 1024 * 1024;
-        config.static_memory_maximum_size(bytes); 
+impl HostModule for NovaHost {
+    fn name(&self) -> &str {
+        "nova"
     }
 
-    let engine = Engine::new(&config)?;
-    let mut linker = Linker::<NovaState>::new(&engine);
-    
-    // 2. Link WASI Preview 1 adapter
-    preview1::add_to_linker_async(&mut linker)?;
+    fn add_to_linker(&self, linker: &mut Linker<NovaState>) -> Result<()> {
+        linker.func_wrap3_async("env", "nova_send", |mut caller: Caller<'_, NovaState>, target_pid: u32, ptr: u32, len: u32| {
+            Box::new(async move {
+                let buf = match read_guest(&mut caller, ptr, len) {
+                    Some(buf) => buf,
+                    None => return,
+                };
 
-    // Link Custom Networking Functions
-    linker.func_wrap("env", "nova_send", |mut caller: Caller<'_, NovaState>, target_pid: u32, ptr: u32, len: u32| {
-        let mem = match caller.get_export("memory") {
-            Some(wasmtime::Extern::Memory(m)) => m,
-            _ => return, // Handle error gracefully in real code
-        };
-        
-        let mut buf = vec![0u8; len as usize];
-        if let Err(_) = mem.read(&caller, ptr as usize, &mut buf) {
-             return; 
-        }
+                if let Some(peers) = &caller.data().config.mesh_peers {
+                    if !peers.contains(&target_pid) {
+                        caller.data().log(LogLevel::Warn, format!("nova_send to pid {} denied: not a declared mesh peer", target_pid));
+                        return;
+                    }
+                }
 
-        // Send asynchronously (spawn a task or block? func_wrap is sync by default unless async func_wrap used)
-        // For prototype, we'll spawn a detached task to send, to avoid blocking the wasm thread heavily.
-        tokio::spawn(async move {
-            let _ = network::GLOBAL_SWITCH.send(target_pid, buf).await;
-        });
-    })?;
+                caller.data().trace_output(|| TraceEvent::Send { target: target_pid, payload: buf.clone() });
+                if caller.data().replaying() {
+                    // Peers are not running during replay
+                    return;
+                }
 
-    // nova_recv(ptr, len) -> bytes_written
-    linker.func_wrap2_async("env", "nova_recv", |mut caller: Caller<'_, NovaState>, ptr: u32, len: u32| {
-        Box::new(async move {
-            let inbox = &mut caller.data_mut().inbox;
-            
-            // Try to receive. This is async!
-            if let Some(msg) = inbox.recv().await {
-                 let mem = match caller.get_export("memory") {
-                    Some(wasmtime::Extern::Memory(m)) => m,
-                    _ => return 0,
+                if caller.data().determinism.is_some() {
+                    // Deliver before the guest continues so inbox order follows program order
+                    let _ = network::GLOBAL_SWITCH.send(target_pid, buf).await;
+                } else {
+                    // Spawn a detached task to send, to avoid blocking the wasm thread heavily.
+                    tokio::spawn(async move {
+                        let _ = network::GLOBAL_SWITCH.send(target_pid, buf).await;
+                    });
+                }
+            })
+        })?;
+
+        // nova_recv(ptr, len) -> bytes_written
+        linker.func_wrap2_async("env", "nova_recv", |mut caller: Caller<'_, NovaState>, ptr: u32, len: u32| {
+            Box::new(async move {
+                // During replay the message comes from the trace instead of the inbox
+                let live = if caller.data().replaying() {
+                    None
+                } else {
+                    loop {
+                        // Waiting for a message is the safe point for checkpoints and export health checks
+                        let state = caller.data_mut();
+                        let request = tokio::select! {
+                            msg = state.inbox.recv() => break msg,
+                            request = snapshot::wait_for_request(state.control.as_ref()) => Some(request),
+                            _ = health::due(state.health.as_ref()) => None,
+                        };
+                        let Some(request) = request else {
+                            check_health(&mut caller).await;
+                            continue;
+                        };
+
+                        let result = capture(&mut caller).and_then(|s| s.write(&request.output));
+                        if let Some(control) = &caller.data().control {
+                            control.complete(&request.output, &result);
+                        }
+                        match result {
+                            Ok(()) if request.stop => return Err(Checkpointed(request.output).into()),
+                            Ok(()) => caller.data().log(LogLevel::Info, format!("checkpoint written to {}", request.output.display())),
+                            Err(e) => caller.data().log(LogLevel::Error, format!("checkpoint failed: {:#}", e)),
+                        }
+                    }
                 };
-                
-                let bytes_to_write = std::cmp::min(len as usize, msg.len());
-                if let Err(_) = mem.write(&mut caller, ptr as usize, &msg[0..bytes_to_write]) {
-                    return 0;
+                let live = match live {
+                    Some(payload) => TraceEvent::Recv { payload, closed: false },
+                    None => TraceEvent::Recv { payload: Vec::new(), closed: true },
+                };
+                let received = match caller.data().trace_input(live) {
+                    TraceEvent::Recv { payload, closed: false } => Some(payload),
+                    _ => None,
+                };
+
+                if let Some(msg) = received {
+                    let bytes_to_write = std::cmp::min(len as usize, msg.len());
+                    if !write_guest(&mut caller, ptr, &msg[..bytes_to_write]) {
+                        return Ok(0);
+                    }
+                    Ok(bytes_to_write as u32)
+                } else {
+                    Ok(0) // Channel closed
                 }
-                bytes_to_write as u32
-            } else {
-                0 // Channel closed
+            })
+        })?;
+
+        // nova_get_pid() -> u32
+        linker.func_wrap("env", "nova_get_pid", |caller: Caller<'_, NovaState>| -> u32 {
+            match caller.data().trace_input(TraceEvent::Pid { pid: caller.data().config.pid }) {
+                TraceEvent::Pid { pid } => pid,
+                _ => caller.data().config.pid,
             }
-        })
-    })?;
+        })?;
+
+        // nova_log(level, ptr, len) - structured logging tagged with pid/container/project
+        linker.func_wrap("env", "nova_log", |mut caller: Caller<'_, NovaState>, level: u32, ptr: u32, len: u32| {
+            let Some(buf) = read_guest(&mut caller, ptr, len) else {
+                return;
+            };
+
+            let level = LogLevel::from_guest(level);
+            let message = String::from_utf8_lossy(&buf).into_owned();
+            caller.data().trace_output(|| TraceEvent::Log {
+                level: level.as_str().to_string(),
+                message: caller.data().redact(message.clone()),
+            });
+            caller.data().log(level, message);
+        })?;
+
+        // nova_clock_ms() -> u64 (wall clock, milliseconds since the Unix epoch)
+        linker.func_wrap("env", "nova_clock_ms", |caller: Caller<'_, NovaState>| -> u64 {
+            let millis = match &caller.data().determinism {
+                Some(determinism) => determinism.time.wall().as_millis() as u64,
+                None => std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+            };
+            match caller.data().trace_input(TraceEvent::ClockMs { millis }) {
+                TraceEvent::ClockMs { millis } => millis,
+                _ => millis,
+            }
+        })?;
+
+        // nova_random(ptr, len) -> 0 on success, -1 if the buffer is out of bounds
+        // or longer than MAX_RANDOM_BYTES
+        linker.func_wrap("env", "nova_random", |mut caller: Caller<'_, NovaState>, ptr: u32, len: u32| -> i32 {
+            let mem = match caller.get_export("memory") {
+                Some(wasmtime::Extern::Memory(m)) => m,
+                _ => return -1,
+            };
+            if len as usize > MAX_RANDOM_BYTES || ptr as usize + len as usize > mem.data_size(&caller) {
+                return -1;
+            }
+
+            let mut buf = vec![0u8; len as usize];
+            match &mut caller.data_mut().determinism {
+                Some(determinism) => determinism.rng.fill_bytes(&mut buf),
+                None => {
+                    for byte in buf.iter_mut() {
+                        *byte = rand::random();
+                    }
+                }
+            }
+            if let TraceEvent::Random { bytes } = caller.data().trace_input(TraceEvent::Random { bytes: buf.clone() }) {
+                buf = bytes;
+            }
+            match mem.write(&mut caller, ptr as usize, &buf) {
+                Ok(_) => 0,
+                Err(_) => -1,
+            }
+        })?;
+
+        // nova_http_fetch(method_ptr, method_len, url_ptr, url_len, body_ptr, body_len, out_ptr, out_cap, out_len_ptr)
+        //   -> HTTP status, or a negative error code (see http::FetchError).
+        // The full body length is stored at out_len_ptr; at most out_cap bytes are copied.
+        linker.func_wrap9_async("env", "nova_http_fetch", |mut caller: Caller<'_, NovaState>,
+            method_ptr: u32, method_len: u32, url_ptr: u32, url_len: u32, body_ptr: u32, body_len: u32,
+            out_ptr: u32, out_cap: u32, out_len_ptr: u32| {
+            Box::new(async move {
+                let invalid = http::FetchError::InvalidRequest.code();
+                let (method, url, body) = match (
+                    read_guest(&mut caller, method_ptr, method_len),
+                    read_guest(&mut caller, url_ptr, url_len),
+                    read_guest(&mut caller, body_ptr, body_len),
+                ) {
+                    (Some(m), Some(u), Some(b)) => (String::from_utf8_lossy(&m).into_owned(), String::from_utf8_lossy(&u).into_owned(), b),
+                    _ => return invalid,
+                };
+
+                // During replay the response comes from the trace and nothing leaves the host
+                let (status, response_body) = if caller.data().replaying() {
+                    (http::FetchError::Failed("not recorded".to_string()).code(), Vec::new())
+                } else {
+                    let policy = caller.data().config.http.clone();
+                    let result = http::fetch(&policy, &method, &url, body).await;
+
+                    // Every egress attempt is logged so it can be audited per container
+                    match result {
+                        Ok(response) => {
+                            caller.data().log(LogLevel::Info, format!("http {} {} -> {}", method, http::log_url(&url), response.status));
+                            (response.status as i32, response.body)
+                        }
+                        Err(e) => {
+                            caller.data().log(LogLevel::Warn, format!("http {} {} {}", method, http::log_url(&url), e));
+                            (e.code(), Vec::new())
+                        }
+                    }
+                };
 
-    // nova_get_pid() -> u32
-    linker.func_wrap("env", "nova_get_pid", |caller: Caller<'_, NovaState>| -> u32 {
-        caller.data().pid
-    })?;
+                // The trace keeps the URL the way the egress log shows it
+                let url = caller.data().redact(http::log_url(&url));
+                let live = TraceEvent::HttpFetch { method, url, status, body: response_body };
+                let (status, response_body) = match caller.data().trace_input(live) {
+                    TraceEvent::HttpFetch { status, body, .. } => (status, body),
+                    _ => return invalid,
+                };
+                if status < 0 {
+                    return status;
+                }
+
+                let mem = match guest_memory(&mut caller) {
+                    Some(m) => m,
+                    None => return invalid,
+                };
+                let copied = std::cmp::min(out_cap as usize, response_body.len());
+                let body_len = (response_body.len() as u32).to_le_bytes();
+                if mem.write(&mut caller, out_ptr as usize, &response_body[..copied]).is_err()
+                    || mem.write(&mut caller, out_len_ptr as usize, &body_len).is_err() {
+                    return invalid;
+                }
+                status
+            })
+        })?;
 
+        Ok(())
+    }
+}
+
+/// The built-in `nova_*` functions followed by the embedder's modules.
+fn all_modules(host_modules: &[Arc<dyn HostModule>]) -> Vec<Arc<dyn HostModule>> {
+    std::iter::once(Arc::new(NovaHost) as Arc<dyn HostModule>)
+        .chain(host_modules.iter().cloned())
+        .collect()
+}
+
+/// The linker guests are instantiated with: WASI preview 1 through the
+/// adapter, then `modules`. `engine` needs async support.
+fn linker(engine: &Engine, modules: &[Arc<dyn HostModule>]) -> Result<Linker<NovaState>> {
+    let mut linker = Linker::new(engine);
+    preview1::add_to_linker_async(&mut linker)?;
+    for module in modules {
+        module.add_to_linker(&mut linker)
+            .map_err(|e| anyhow::anyhow!("Failed to link host module '{}': {}", module.name(), e))?;
+    }
+    Ok(linker)
+}
+
+/// Instantiate-time check of a module against the linker `run_wasm` would
+/// use with `host_modules`: everything it imports must be defined there
+/// with the same type.
+pub fn check_imports(module: &[u8], host_modules: &[Arc<dyn HostModule>]) -> Result<()> {
+    let mut wasm_config = Config::new();
+    wasm_config.async_support(true);
+    let engine = Engine::new(&wasm_config)?;
+    let linker = linker(&engine, &all_modules(host_modules))?;
+    linker.instantiate_pre(&Module::new(&engine, module)?)?;
+    Ok(())
+}
+
+pub async fn run_wasm(path: &str, config: RunConfig) -> Result<()> {
+    // 1. Configure Wasmtime
+    let mut wasm_config = Config::new();
+    wasm_config.async_support(true);
+    wasm_config.consume_fuel(true); // Enable fuel metering
+    if config.debug {
+        // Unoptimized code keeps guest locals where the debugger can find them
+        wasm_config.debug_info(true);
+        wasm_config.native_unwind_info(true);
+        wasm_config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+        wasm_config.cranelift_opt_level(wasmtime::OptLevel::None);
+    } else {
+        wasm_config.cranelift_opt_level(wasmtime::OptLevel::Speed);
+    }
+    if config.deterministic.is_some() {
+        // NaN bit patterns otherwise depend on the host CPU
+        wasm_config.cranelift_nan_canonicalization(true);
+    }
+    if config.profile.is_some() || config.metrics_interval.is_some() {
+        // Profile samples and the fuel readings behind periodic reports are taken on epoch ticks
+        wasm_config.epoch_interruption(true);
+    }
+    if config.perfmap {
+        wasm_config.profiler(wasmtime::ProfilingStrategy::PerfMap);
+    }
+
+    if let Some(mb) = config.memory_limit_mb {
+        let bytes = mb * 1024 * 1024;
+        wasm_config.static_memory_maximum_size(bytes); 
+    }
+
+    let engine = Engine::new(&wasm_config)?;
+
+    // 2. Link WASI Preview 1 adapter and host modules
+    let modules = all_modules(&config.host_modules);
+    let linker = linker(&engine, &modules)?;
 
     // 3. Create context
     let table = Table::new();
     let mut builder = WasiCtxBuilder::new();
     builder.inherit_stdio();
 
-    if let Some(map_dir) = map_dir {
+    if let Some(map_dir) = &config.map_dir {
         let parts: Vec<&str> = map_dir.split(':').collect();
         if parts.len() == 2 {
             let host_path = parts[0];
@@ -116,25 +602,560 @@ pub async fn run_wasm(path: &str, pid: u32, fuel: u64, memory_limit_mb: Option<u
         }
     }
 
+    for (host_path, guest_path) in &config.volumes {
+        std::fs::create_dir_all(host_path)?;
+        println!("💾 Volume '{}' at '{}'", host_path.display(), guest_path);
+        let dir = std::fs::File::open(host_path)?;
+        builder.preopened_dir(
+            wasmtime_wasi::Dir::from_std_file(dir),
+            wasmtime_wasi::preview2::DirPerms::all(),
+            wasmtime_wasi::preview2::FilePerms::all(),
+            guest_path,
+        );
+    }
+
+    builder.arg(path);
+    for arg in &config.args {
+        builder.arg(arg);
+    }
+    for (key, value) in &config.env {
+        builder.env(key, value);
+    }
+
+    let _secret_files = match &config.secrets_dir {
+        Some(guest_path) if !config.secrets.is_empty() => {
+            let files = SecretFiles::create(&config.secrets)?;
+            let dir = std::fs::File::open(&files.dir)?;
+            builder.preopened_dir(
+                wasmtime_wasi::Dir::from_std_file(dir),
+                wasmtime_wasi::preview2::DirPerms::READ,
+                wasmtime_wasi::preview2::FilePerms::READ,
+                guest_path,
+            );
+            Some(files)
+        }
+        _ => {
+            for secret in &config.secrets {
+                builder.env(&secret.name, &secret.value);
+            }
+            None
+        }
+    };
+
+    let determinism = config.deterministic.map(|seed| {
+        let determinism = Determinism::new(seed, config.pid);
+        let (secure, insecure) = deterministic::wasi_rngs(seed, config.pid);
+        builder.wall_clock(VirtualWallClock(determinism.time.clone()));
+        builder.monotonic_clock(VirtualMonotonicClock(determinism.time.clone()));
+        builder.secure_random(secure);
+        builder.insecure_random(insecure);
+        builder.insecure_random_seed(((seed as u128) << 64) | config.pid as u128);
+        determinism
+    });
+
+    // Clock reads, random bytes and stdout/stderr writes pass through the trace
+    // when recording or replaying
+    if let Some(trace) = &config.trace {
+        let (wall, monotonic, secure, insecure): TracedSources =
+            match (&determinism, config.deterministic) {
+                (Some(determinism), Some(seed)) => {
+                    let (secure, insecure) = deterministic::wasi_rngs(seed, config.pid);
+                    (
+                        Box::new(VirtualWallClock(determinism.time.clone())),
+                        Box::new(VirtualMonotonicClock(determinism.time.clone())),
+                        Box::new(secure),
+                        Box::new(insecure),
+                    )
+                }
+                _ => (
+                    Box::new(trace::SystemWallClock),
+                    Box::new(trace::SystemMonotonicClock::default()),
+                    Box::new(StdRng::from_seed(rand::random())),
+                    Box::new(StdRng::from_seed(rand::random())),
+                ),
+            };
+        builder.wall_clock(TracedWallClock { inner: wall, trace: trace.clone() });
+        builder.monotonic_clock(TracedMonotonicClock { inner: monotonic, trace: trace.clone() });
+        builder.secure_random(TracedRng { inner: secure, trace: trace.clone() });
+        builder.insecure_random(TracedRng { inner: insecure, trace: trace.clone() });
+        let secrets = Arc::new(config.secrets.iter().map(|secret| secret.value.clone()).collect::<Vec<_>>());
+        builder.stdout(TracedStdout { fd: 1, secrets: secrets.clone(), trace: trace.clone() });
+        builder.stderr(TracedStdout { fd: 2, secrets, trace: trace.clone() });
+    }
+
     let ctx = builder.build();
     let adapter = preview1::WasiPreview1Adapter::new();
 
     // Register with the global switch
-    let inbox = network::GLOBAL_SWITCH.register(pid);
+    let inbox = network::GLOBAL_SWITCH.register(config.pid);
 
-    let state = NovaState { table, ctx, adapter, pid, inbox };
+    // 4. Load module. Instances that can be checkpointed or are restored export
+    // their globals, memories and tables so the snapshot code can reach them
+    let wasm = read_module(std::path::Path::new(path))?;
+    let receives = snapshot::imports_recv(&wasm)?;
+    if config.checkpoint && !receives {
+        anyhow::bail!("{} does not import nova_recv, so it never reaches a safe point to checkpoint at", path);
+    }
+    if config.checkpoint || config.restore.is_some() {
+        snapshot::check_checkpointable(&wasm)?;
+    }
+    let original_len = wasm.len();
+    let (wasm, layout) = if config.restore.is_some() {
+        snapshot::instrument_for_restore(&wasm)?
+    } else if config.checkpoint {
+        snapshot::instrument(&wasm)?
+    } else {
+        (wasm, StateLayout::default())
+    };
+    // Instrumenting only changes the export and start sections, which come
+    // before the code, so code offsets in the user's file differ by the
+    // change in size
+    let offset_shift = wasm.len() as i64 - original_len as i64;
+    let module = Module::new(&engine, &wasm)?;
+
+    let control = if config.checkpoint && config.trace.is_none() {
+        Some(ControlDir::create(&config.container, config.pid)?)
+    } else {
+        None
+    };
+
+    let profiler = config.profile.as_ref()
+        .map(|_| GuestProfiler::new(path, PROFILE_INTERVAL, vec![(path.to_string(), module.clone())]));
+
+    let fuel = config.fuel;
+    let usage = Usage::new(fuel, config.memory_limit_mb.map(|mb| mb * 1024 * 1024));
+    let mut extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>> = HashMap::new();
+    for host_module in &modules {
+        if let Some(state) = host_module.state(&config)? {
+            let id = (*state).type_id();
+            if extensions.insert(id, state).is_some() {
+                anyhow::bail!("Host module '{}' registers a state type that is already registered", host_module.name());
+            }
+        }
+    }
+
+    // Probes would change what a deterministic run or a replay does
+    let probe = if config.deterministic.is_none() && config.trace.is_none() { config.healthcheck.clone() } else { None };
+    let (health, _http_probe) = match probe {
+        Some(check) if matches!(check.probe, Probe::Export(_)) => {
+            // Export probes run while the guest waits in nova_recv
+            if !receives {
+                anyhow::bail!("{} has an export HEALTHCHECK but does not import nova_recv, so the probe could never run", path);
+            }
+            (Some(HealthMonitor::new(check)), None)
+        }
+        Some(check) => {
+            let sink = config.log_sink.clone();
+            let (pid, container, project, deployment) = (config.pid, config.container.clone(), config.project.clone(), config.deployment.clone());
+            let report = move |level, message| sink(&LogRecord {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level,
+                pid,
+                container: container.clone(),
+                project: project.clone(),
+                deployment: deployment.clone(),
+                message,
+            });
+            // Stops when `_http_probe` is dropped at the end of the run
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = stopped => {}
+                    _ = health::probe_http(check, report) => {}
+                }
+            });
+            (None, Some(stop))
+        }
+        None => (None, None),
+    };
+
+    let state = NovaState { table, ctx, adapter, config, inbox, determinism, module: path.to_string(), layout, control, profiler, usage, health, extensions };
     let mut store = Store::new(&engine, state);
     store.set_fuel(fuel)?; // Set the fuel limit
+    store.limiter(|state| &mut state.usage);
+    if store.data().determinism.is_some() {
+        store.fuel_async_yield_interval(Some(deterministic::YIELD_INTERVAL))?;
+    }
 
-    // 4. Load module
-    let module = Module::from_file(&engine, path)?;
+    // Periodic reports come from a host timer, so an instance blocked in a
+    // host call (nova_recv, a WASI sleep) still reports. Fuel only moves
+    // while guest code runs; epoch ticks keep the observed value current then.
+    // Both tasks stop when `_ticker` and `_reporter` are dropped at the end of the run.
+    let _reporter = store.data().config.metrics_interval.map(|interval| {
+        let (usage, sink, template) = (store.data().usage.clone(), store.data().config.metrics_sink.clone(), store.data().run_report(false));
+        let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let mut reports = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = reports.tick() => sink(&usage.report(template.clone())),
+                }
+            }
+        });
+        stop
+    });
+    let observe = store.data().config.metrics_interval.is_some();
+    let _ticker = if store.data().profiler.is_some() || observe {
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |mut cx| {
+            if let Some(mut profiler) = cx.data_mut().profiler.take() {
+                profiler.sample(&cx);
+                cx.data_mut().profiler = Some(profiler);
+            }
+            if observe {
+                cx.data().usage.observe_fuel(cx.get_fuel()?);
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+
+        let engine = engine.clone();
+        let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROFILE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = interval.tick() => engine.increment_epoch(),
+                }
+            }
+        });
+        Some(stop)
+    } else {
+        None
+    };
 
     // 5. Instantiate
     let instance: Instance = linker.instantiate_async(&mut store, &module).await?;
 
-    // 6. Run
-    let start_func = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
-    start_func.call_async(&mut store, ()).await?;
+    // 6. Run, or resume a checkpoint
+    let result = match store.data().config.restore.clone() {
+        Some(snapshot) => {
+            restore_state(&mut store, &instance, &snapshot)?;
+            let resume = instance.get_typed_func::<(), ()>(&mut store, "nova_resume")
+                .map_err(|_| anyhow::anyhow!("{} does not export nova_resume, so it cannot be restored", path))?;
+            resume.call_async(&mut store, ()).await
+        }
+        None => {
+            let start_func = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+            start_func.call_async(&mut store, ()).await
+        }
+    };
+
+    store.data().usage.observe_fuel(store.get_fuel().unwrap_or(0));
+    let report = store.data().run_report(true);
+    (store.data().config.metrics_sink)(&report);
+
+    if let Some(profiler) = store.data_mut().profiler.take() {
+        let out = store.data().config.profile.clone().unwrap_or_default();
+        profiler.finish(std::fs::File::create(&out)?)?;
+        println!("🔥 Profile written to {} (open it at https://profiler.firefox.com)", out.display());
+    }
+
+    if let Some(trace) = &store.data().config.trace {
+        trace.output(TraceEvent::Exit { error: result.as_ref().err().map(|e| e.to_string()) });
+        trace.finish()?;
+    }
+    if let Err(e) = &result {
+        if let Some(checkpointed) = e.downcast_ref::<Checkpointed>() {
+            println!("⏸️  Container '{}' {}", store.data().config.container, checkpointed);
+            return Ok(());
+        }
+        if store.data().config.debug {
+            if let Some(backtrace) = e.downcast_ref::<WasmBacktrace>() {
+                eprintln!("💥 {} trapped: {}\n{}", path, e.root_cause(), format_backtrace(backtrace, offset_shift));
+            }
+        }
+    }
+    result?;
+
+    Ok(())
+}
+
+/// Call the image's health-check export and log what changed.
+async fn check_health(caller: &mut Caller<'_, NovaState>) {
+    let Some(Probe::Export(name)) = caller.data().health.as_ref().map(|monitor| monitor.check.probe.clone()) else {
+        return;
+    };
+    let result = match caller.get_export(&name).and_then(|export| export.into_func()) {
+        Some(func) => match func.typed::<(), i32>(&*caller) {
+            Ok(func) => match func.call_async(&mut *caller, ()).await {
+                Ok(0) => Ok(()),
+                Ok(status) => Err(anyhow::anyhow!("{} returned {}", name, status)),
+                Err(e) => Err(anyhow::anyhow!("{} trapped: {}", name, e)),
+            },
+            Err(_) => Err(anyhow::anyhow!("{} is not a () -> i32 function", name)),
+        },
+        None => Err(anyhow::anyhow!("module does not export {}", name)),
+    };
+    let change = caller.data_mut().health.as_mut().and_then(|monitor| monitor.record(result));
+    if let Some((level, message)) = change {
+        caller.data().log(level, message);
+    }
+}
+
+/// Read a module from disk. Anything without the `\0asm` header is compiled
+/// as WAT, so text syntax errors point at file:line:column.
+pub fn read_module(path: &std::path::Path) -> Result<Vec<u8>> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    if bytes.starts_with(&WASM_MAGIC) {
+        return Ok(bytes);
+    }
+    wat::parse_file(path).map_err(|e| anyhow::anyhow!("Failed to compile WAT module: {}", e))
+}
+
+/// One line per frame, innermost first, with source locations when the
+/// module carries DWARF. Offsets are taken back by `offset_shift` so they
+/// point into the module file rather than its instrumented copy.
+fn format_backtrace(backtrace: &WasmBacktrace, offset_shift: i64) -> String {
+    let mut out = String::new();
+    for (i, frame) in backtrace.frames().iter().enumerate() {
+        let name = frame.func_name().map(String::from)
+            .unwrap_or_else(|| format!("<func {}>", frame.func_index()));
+        let offset = frame.module_offset().map(|o| format!(" @ {:#x}", (o as i64 - offset_shift).max(0))).unwrap_or_default();
+        out.push_str(&format!("  {:>3}: {}{}\n", i, name, offset));
+
+        for symbol in frame.symbols() {
+            let Some(file) = symbol.file() else { continue };
+            let function = symbol.name().unwrap_or("?");
+            match (symbol.line(), symbol.column()) {
+                (Some(line), Some(column)) => out.push_str(&format!("         at {} ({}:{}:{})\n", function, file, line, column)),
+                (Some(line), None) => out.push_str(&format!("         at {} ({}:{})\n", function, file, line)),
+                _ => out.push_str(&format!("         at {} ({})\n", function, file)),
+            }
+        }
+    }
+    out
+}
+
+/// Read the instance's state while it is parked in `nova_recv`.
+fn capture(caller: &mut Caller<'_, NovaState>) -> Result<Snapshot> {
+    let layout = caller.data().layout;
+
+    let mut globals = Vec::new();
+    for i in 0..layout.globals {
+        let global = caller.get_export(&snapshot::global_export(i))
+            .and_then(|e| e.into_global())
+            .ok_or_else(|| anyhow::anyhow!("Global {} is not exported", i))?;
+        if global.ty(&*caller).mutability() != Mutability::Var {
+            globals.push(GlobalValue::Immutable);
+            continue;
+        }
+        globals.push(match global.get(&mut *caller) {
+            Val::I32(v) => GlobalValue::I32(v),
+            Val::I64(v) => GlobalValue::I64(v),
+            Val::F32(v) => GlobalValue::F32(v),
+            Val::F64(v) => GlobalValue::F64(v),
+            _ => anyhow::bail!("Global {} holds a reference or vector and cannot be checkpointed", i),
+        });
+    }
+
+    let mut memories = Vec::new();
+    for i in 0..layout.memories {
+        let memory = caller.get_export(&snapshot::memory_export(i))
+            .and_then(|e| e.into_memory())
+            .ok_or_else(|| anyhow::anyhow!("Memory {} is not exported", i))?;
+        memories.push(memory.data(&*caller).to_vec());
+    }
+
+    let mut tables = Vec::new();
+    for i in 0..layout.tables {
+        let table = caller.get_export(&snapshot::table_export(i))
+            .and_then(|e| e.into_table())
+            .ok_or_else(|| anyhow::anyhow!("Table {} is not exported", i))?;
+        tables.push(table.size(&*caller) as u64);
+    }
+
+    let fuel = caller.get_fuel()?;
+    let state = caller.data();
+    Ok(Snapshot {
+        header: SnapshotHeader {
+            module: state.module.clone(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            container: state.config.container.clone(),
+            pid: state.config.pid,
+            args: state.config.args.clone(),
+            env: state.config.env.clone(),
+            map_dir: state.config.map_dir.clone(),
+            volumes: state.config.volumes.clone(),
+            secrets: state.config.secrets.iter().map(|secret| secret.name.clone()).collect(),
+            secrets_dir: state.config.secrets_dir.clone(),
+            memory_limit_mb: state.config.memory_limit_mb,
+            fuel,
+            globals,
+            tables,
+            memories: Vec::new(),
+        },
+        memories,
+    })
+}
+
+/// Write a checkpoint's memories, mutable globals and table sizes into a fresh instance.
+fn restore_state(store: &mut Store<NovaState>, instance: &Instance, snapshot: &Snapshot) -> Result<()> {
+    if snapshot.layout() != store.data().layout {
+        anyhow::bail!("Snapshot was taken from a different module than {}", store.data().module);
+    }
+
+    for (i, data) in snapshot.memories.iter().enumerate() {
+        let memory = instance.get_memory(&mut *store, &snapshot::memory_export(i as u32))
+            .ok_or_else(|| anyhow::anyhow!("Memory {} is not exported", i))?;
+        let current = memory.data_size(&*store);
+        if data.len() > current {
+            let pages = (data.len() - current).div_ceil(snapshot::PAGE_SIZE);
+            memory.grow(&mut *store, pages as u64)?;
+        }
+        memory.write(&mut *store, 0, data)?;
+    }
+
+    for (i, value) in snapshot.header.globals.iter().enumerate() {
+        let value = match value {
+            GlobalValue::I32(v) => Val::I32(*v),
+            GlobalValue::I64(v) => Val::I64(*v),
+            GlobalValue::F32(v) => Val::F32(*v),
+            GlobalValue::F64(v) => Val::F64(*v),
+            GlobalValue::Immutable => continue,
+        };
+        let global = instance.get_global(&mut *store, &snapshot::global_export(i as u32))
+            .ok_or_else(|| anyhow::anyhow!("Global {} is not exported", i))?;
+        global.set(&mut *store, value)?;
+    }
+
+    for (i, size) in snapshot.header.tables.iter().enumerate() {
+        let table = instance.get_table(&mut *store, &snapshot::table_export(i as u32))
+            .ok_or_else(|| anyhow::anyhow!("Table {} is not exported", i))?;
+        let current = table.size(&*store) as u64;
+        if *size > current {
+            let null = match table.ty(&*store).element() {
+                ValType::ExternRef => Val::ExternRef(None),
+                _ => Val::FuncRef(None),
+            };
+            table.grow(&mut *store, (*size - current) as u32, null)?;
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_guest_logs_reach_the_sink() {
+        // The oversized log and random requests must fail without allocating
+        let path = std::env::temp_dir().join(format!("nova_log_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"(module
+            (import "env" "nova_log" (func $log (param i32 i32 i32)))
+            (import "env" "nova_random" (func $random (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "hello")
+            (func (export "_start")
+                (call $log (i32.const 3) (i32.const 0) (i32.const 5))
+                (call $log (i32.const 2) (i32.const 0) (i32.const -1))
+                (if (i32.ne (call $random (i32.const 0) (i32.const -1)) (i32.const -1)) (then unreachable))
+                (if (i32.ne (call $random (i32.const 0) (i32.const 16)) (i32.const 0)) (then unreachable))))"#).unwrap();
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let mut config = RunConfig::new("app/web:1.0");
+        config.pid = 7;
+        config.log_sink = {
+            let records = records.clone();
+            Arc::new(move |record: &LogRecord| records.lock().unwrap().push(record.clone()))
+        };
+        let result = run_wasm(&path.to_string_lossy(), config).await;
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].level, records[0].pid), (LogLevel::Warn, 7));
+        assert_eq!((records[0].container.as_str(), records[0].message.as_str()), ("app/web:1.0", "hello"));
+        assert!(logging::log_path(&records[0].container).ends_with(".nova/logs/app-web_1.0.log"));
+    }
+
+    #[tokio::test]
+    async fn test_export_healthcheck_needs_recv() {
+        let path = std::env::temp_dir().join(format!("nova_health_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"(module (func (export "health") (result i32) (i32.const 0)) (func (export "_start")))"#).unwrap();
+
+        let mut config = RunConfig::new(format!("health-test-{}", uuid::Uuid::new_v4()));
+        config.healthcheck = Healthcheck::parse(&["export".to_string(), "health".to_string()]).unwrap();
+        let result = run_wasm(&path.to_string_lossy(), config).await;
+        let _ = std::fs::remove_file(&path);
+        assert!(result.unwrap_err().to_string().contains("nova_recv"));
+    }
+
+    #[tokio::test]
+    async fn test_only_checkpointable_instances_claim_the_control_dir() {
+        let path = std::env::temp_dir().join(format!("nova_control_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"(module (import "env" "nova_recv" (func (param i32 i32) (result i32))) (func (export "_start")))"#).unwrap();
+        let container = format!("control-test-{}", uuid::Uuid::new_v4());
+        let running = ControlDir::create(&container, 0).unwrap();
+
+        // A second instance of the same container and pid runs unless it asks for checkpoints
+        let plain = run_wasm(&path.to_string_lossy(), RunConfig::new(container.clone())).await;
+        let mut config = RunConfig::new(container.clone());
+        config.checkpoint = true;
+        let checkpointable = run_wasm(&path.to_string_lossy(), config).await;
+        let _ = std::fs::remove_file(&path);
+        drop(running);
+
+        plain.unwrap();
+        assert!(checkpointable.unwrap_err().to_string().contains("already has a running instance"));
+    }
+
+    #[tokio::test]
+    async fn test_reports_while_blocked() {
+        // The guest does nothing but wait in nova_recv; reports still arrive
+        let path = std::env::temp_dir().join(format!("nova_metrics_{}.wat", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"(module
+            (import "env" "nova_recv" (func $recv (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "_start") (drop (call $recv (i32.const 0) (i32.const 16)))))"#).unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut config = RunConfig::new(format!("metrics-test-{}", uuid::Uuid::new_v4()));
+        config.pid = 40_000 + rand::random::<u32>() % 10_000;
+        config.metrics_interval = Some(Duration::from_millis(100));
+        config.metrics_sink = {
+            let reports = reports.clone();
+            Arc::new(move |report: &RunReport| reports.lock().unwrap().push(report.finished))
+        };
+
+        let pid = config.pid;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(450)).await;
+            network::GLOBAL_SWITCH.send(pid, b"wake".to_vec()).await.unwrap();
+        });
+        let result = run_wasm(&path.to_string_lossy(), config).await;
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.iter().filter(|finished| !**finished).count() >= 3, "{:?}", reports);
+        assert_eq!(reports.last(), Some(&true));
+    }
+
+    #[test]
+    fn test_backtrace_offsets_point_into_the_original_module() {
+        let original = wat::parse_str(r#"(module
+            (global (mut i32) (i32.const 0))
+            (func (export "_start") (nop) (unreachable)))"#).unwrap();
+        let (instrumented, _) = snapshot::instrument(&original).unwrap();
+
+        let engine = Engine::default();
+        let trap = |wasm: &[u8], offset_shift: i64| {
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &Module::new(&engine, wasm).unwrap(), &[]).unwrap();
+            let start = instance.get_typed_func::<(), ()>(&mut store, "_start").unwrap();
+            let error = start.call(&mut store, ()).unwrap_err();
+            format_backtrace(error.downcast_ref::<WasmBacktrace>().unwrap(), offset_shift)
+        };
+
+        let expected = trap(&original, 0);
+        assert!(expected.contains(" @ 0x"), "{}", expected);
+        assert_eq!(trap(&instrumented, instrumented.len() as i64 - original.len() as i64), expected);
+    }
+}
@@ -3,8 +3,13 @@ use std::path::{Path, PathBuf};
 use std::fs;
 use serde_json::Value;
 
+/// First four bytes of every binary Wasm module.
+pub const WASM_MAGIC: [u8; 4] = *b"\0asm";
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Language {
+    /// Binary `.wasm` or text `.wat` module, run directly by Wasmtime.
+    Wasm,
     Python,
     JavaScript,
     TypeScript,
@@ -60,11 +65,31 @@ impl RuntimeDetector {
         }
     }
     
+    /// Map a `--runtime` name onto the language it runs.
+    pub fn parse_runtime(name: &str) -> Result<Language> {
+        Ok(match name.to_lowercase().as_str() {
+            "wasm" | "wat" | "wasmtime" => Language::Wasm,
+            "python" | "py" => Language::Python,
+            "node" | "nodejs" | "javascript" | "js" => Language::JavaScript,
+            "typescript" | "ts" => Language::TypeScript,
+            _ => anyhow::bail!("Unknown runtime '{}' (expected wasm, python or node)", name),
+        })
+    }
+    
     /// Detect language from file extension or project structure
     pub fn detect_language(&self) -> Result<Language> {
+        // Binary modules are recognized by content, whatever they are named
+        if self.path.is_file() && self.has_wasm_magic() {
+            return Ok(Language::Wasm);
+        }
+        
         // Check file extension
         if let Some(ext) = self.path.extension() {
             return Ok(match ext.to_str() {
+                Some("wasm") if self.path.exists() => {
+                    anyhow::bail!("{} is not a Wasm binary (missing \\0asm header)", self.path.display())
+                }
+                Some("wasm") | Some("wat") => Language::Wasm,
                 Some("py") => Language::Python,
                 Some("js") => Language::JavaScript,
                 Some("ts") => Language::TypeScript,
@@ -203,6 +228,13 @@ impl RuntimeDetector {
         Ok(Framework::None)
     }
     
+    fn has_wasm_magic(&self) -> bool {
+        let mut header = [0u8; 4];
+        fs::File::open(&self.path)
+            .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header))
+            .is_ok_and(|_| header == WASM_MAGIC)
+    }
+    
     fn file_exists(&self, name: &str) -> bool {
         self.path.join(name).exists()
     }
@@ -253,4 +285,16 @@ mod tests {
         let detector = RuntimeDetector::new("server.js");
         assert_eq!(detector.detect_language().unwrap(), Language::JavaScript);
     }
+    
+    #[test]
+    fn test_detect_wasm() {
+        assert_eq!(RuntimeDetector::new("hello.wat").detect_language().unwrap(), Language::Wasm);
+        
+        // Sniffed by content when the extension says nothing
+        let path = std::env::temp_dir().join(format!("nova-detect-{}", std::process::id()));
+        fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+        let language = RuntimeDetector::new(&path).detect_language().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(language, Language::Wasm);
+    }
 }
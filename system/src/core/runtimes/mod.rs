@@ -19,7 +19,7 @@ pub trait Runtime {
 }
 
 /// Resource limits for containers
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ResourceLimits {
     pub memory_mb: Option<u64>,
     #[allow(dead_code)]
@@ -28,16 +28,6 @@ pub struct ResourceLimits {
     pub fuel: Option<u64>,
 }
 
-impl Default for ResourceLimits {
-    fn default() -> Self {
-        Self {
-            memory_mb: None,
-            cpu_percent: None,
-            fuel: None,
-        }
-    }
-}
-
 /// Apply resource limits to a command (platform-specific)
 pub fn apply_limits(_cmd: &mut Command, limits: &ResourceLimits) {
     #[cfg(target_os = "linux")]
@@ -5,6 +5,7 @@ use std::fs;
 use serde_json::Value;
 use super::{Runtime, ResourceLimits, apply_limits};
 
+#[derive(Default)]
 pub struct NodeJSRuntime {
     pub limits: ResourceLimits,
 }
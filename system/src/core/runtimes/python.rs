@@ -3,6 +3,7 @@ use std::process::{Command, Stdio};
 use std::path::Path;
 use super::{Runtime, ResourceLimits, apply_limits};
 
+#[derive(Default)]
 pub struct PythonRuntime {
     pub limits: ResourceLimits,
 }
@@ -1,13 +1,18 @@
 use anyhow::Result;
 use std::sync::Arc;
 use tokio::task;
-use crate::core::runtime;
+use crate::core::runtime::{self, RunConfig};
 
 pub struct Scheduler;
 
 impl Scheduler {
     /// Run `replicas` instances of the Wasm container at `path`.
-    pub async fn run_replicas(path: String, replicas: u32, fuel: u64, memory: Option<u64>, map_dir: Option<String>) -> Result<()> {
+    /// Each replica gets `config` with its own pid (0..replicas).
+    pub async fn run_replicas(path: String, replicas: u32, config: RunConfig) -> Result<()> {
+        if config.deterministic.is_some() {
+            return Self::run_replicas_deterministic(path, replicas, config).await;
+        }
+
         let path = Arc::new(path);
         let mut handles = Vec::new();
 
@@ -15,13 +20,12 @@ impl Scheduler {
 
         for i in 0..replicas {
             let path_clone = path.clone();
-            let map_dir_clone = map_dir.clone(); // Clone per task
+            let mut replica_config = config.clone(); // Clone per task
+            replica_config.pid = i;
             let handle = task::spawn(async move {
-                // In a real system, we'd capture stdout per-container.
-                // For now, we just let them print to the shared stdout.
-                // We add a tiny identifier to the output if possible, but run_wasm is generic.
-                // Let's just run it.
-                match runtime::run_wasm(&path_clone, i, fuel, memory, map_dir_clone).await {
+                // Guest output goes to the shared stdout; structured records
+                // written through `nova_log` are tagged with the replica pid.
+                match runtime::run_wasm(&path_clone, replica_config).await {
                     Ok(_) => println!("✅ Replica #{} finished.", i),
                     Err(e) => eprintln!("❌ Replica #{} failed: {}", i, e),
                 }
@@ -37,4 +41,40 @@ impl Scheduler {
         println!("🏁 All replicas completed.");
         Ok(())
     }
+
+    /// Deterministic variant: all replicas share one single-threaded executor
+    /// on a dedicated thread and are polled in pid order. Combined with
+    /// fuel-based yielding, message interleaving depends only on the
+    /// instructions each replica executes.
+    async fn run_replicas_deterministic(path: String, replicas: u32, config: RunConfig) -> Result<()> {
+        println!("🚀 Scheduler: Spawning {} replicas (deterministic, seed {})...", replicas, config.deterministic.unwrap_or_default());
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map(|rt| rt.block_on(async move {
+                    let runs = (0..replicas).map(|i| {
+                        let path = path.clone();
+                        let mut replica_config = config.clone();
+                        replica_config.pid = i;
+                        async move { (i, runtime::run_wasm(&path, replica_config).await) }
+                    });
+
+                    for (i, result) in futures_util::future::join_all(runs).await {
+                        match result {
+                            Ok(_) => println!("✅ Replica #{} finished.", i),
+                            Err(e) => eprintln!("❌ Replica #{} failed: {}", i, e),
+                        }
+                    }
+                }));
+            let _ = tx.send(result);
+        });
+
+        rx.await??;
+
+        println!("🏁 All replicas completed.");
+        Ok(())
+    }
 }
@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const KEY_FILE: &str = "secret.key";
+const STORE_FILE: &str = "secrets.json";
+const NONCE_LEN: usize = 12;
+
+/// A resolved secret handed to a container at start. The value never
+/// shows up in `Debug` output.
+#[derive(Clone)]
+pub struct SecretValue {
+    pub name: String,
+    pub value: String,
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretValue").field("name", &self.name).field("value", &"***").finish()
+    }
+}
+
+/// Secret names double as environment variable names, so keep them to
+/// `[A-Za-z_][A-Za-z0-9_]*`.
+pub fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    };
+    if !valid {
+        anyhow::bail!("Invalid secret name '{}': use letters, digits and '_' (not starting with a digit)", name);
+    }
+    Ok(())
+}
+
+/// Load the local encryption key from `dir`, creating it on first use.
+pub fn load_or_create_key(dir: &Path) -> Result<Key> {
+    let key_path = dir.join(KEY_FILE);
+    if key_path.exists() {
+        let bytes = fs::read(&key_path).context("Failed to read secret key")?;
+        if bytes.len() != 32 {
+            anyhow::bail!("Secret key at {} is corrupt", key_path.display());
+        }
+        return Ok(*Key::from_slice(&bytes));
+    }
+
+    fs::create_dir_all(dir)?;
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    write_private(&key_path, key.as_slice())?;
+    Ok(key)
+}
+
+/// Encrypt a value as base64(nonce || ciphertext).
+pub fn encrypt(key: &Key, plaintext: &str) -> Result<String> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt secret"))?;
+
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(base64::engine::general_purpose::STANDARD.encode(out))
+}
+
+/// Reverse of `encrypt`.
+pub fn decrypt(key: &Key, encoded: &str) -> Result<String> {
+    let raw = base64::engine::general_purpose::STANDARD.decode(encoded)
+        .context("Secret is not valid base64")?;
+    if raw.len() < NONCE_LEN {
+        anyhow::bail!("Secret ciphertext is truncated");
+    }
+
+    let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(key);
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt secret (wrong key?)"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Create `path` readable by its owner only, replacing any file there. The
+/// file is created with mode 0600, never chmod-ed after the fact.
+pub fn create_private(path: &Path) -> Result<fs::File> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).with_context(|| format!("Failed to create {}", path.display()))
+}
+
+/// Write a file only its owner can read. The contents go to a private
+/// temporary file that is renamed over `path`, so the file is never left
+/// half-written.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = create_private(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredSecret {
+    ciphertext: String,
+    updated_at: String,
+}
+
+/// Local secret store behind `nova secret`: `~/.nova/secrets.json`,
+/// encrypted with the key in `~/.nova/secret.key`.
+pub struct SecretStore {
+    dir: PathBuf,
+    key: Key,
+    entries: BTreeMap<String, StoredSecret>,
+}
+
+/// `~/.nova`, where the key and the local store live.
+pub fn default_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(format!("{}/.nova", home))
+}
+
+impl SecretStore {
+    pub fn open_default() -> Result<Self> {
+        Self::open(&default_dir())
+    }
+
+    pub fn open(dir: &Path) -> Result<Self> {
+        let key = load_or_create_key(dir)?;
+        let store_path = dir.join(STORE_FILE);
+        let entries = if store_path.exists() {
+            let content = fs::read_to_string(&store_path).context("Failed to read secret store")?;
+            serde_json::from_str(&content).context("Failed to parse secret store")?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(SecretStore { dir: dir.to_path_buf(), key, entries })
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        self.entries.insert(name.to_string(), StoredSecret {
+            ciphertext: encrypt(&self.key, value)?,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        });
+        self.save()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        match self.entries.get(name) {
+            Some(secret) => Ok(Some(decrypt(&self.key, &secret.ciphertext)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Secret names with their last update time. Values are never listed.
+    pub fn list(&self) -> Vec<(String, String)> {
+        self.entries.iter()
+            .map(|(name, secret)| (name.clone(), secret.updated_at.clone()))
+            .collect()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let removed = self.entries.remove(name).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Resolve every name in `names`, failing on the first one that is not set.
+    pub fn resolve(&self, names: &[String]) -> Result<Vec<SecretValue>> {
+        names.iter()
+            .map(|name| match self.get(name)? {
+                Some(value) => Ok(SecretValue { name: name.clone(), value }),
+                None => anyhow::bail!("Secret '{}' is not set. Run `nova secret set {}` first.", name, name),
+            })
+            .collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.entries)?;
+        write_private(&self.dir.join(STORE_FILE), json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("nova_secrets_{}", uuid::Uuid::new_v4()));
+
+        let mut store = SecretStore::open(&dir).unwrap();
+        store.set("DB_PASSWORD", "hunter2").unwrap();
+
+        // Nothing is stored in plaintext
+        let on_disk = fs::read_to_string(dir.join(STORE_FILE)).unwrap();
+        assert!(!on_disk.contains("hunter2"));
+
+        let store = SecretStore::open(&dir).unwrap();
+        assert_eq!(store.get("DB_PASSWORD").unwrap().as_deref(), Some("hunter2"));
+        assert!(store.resolve(&["MISSING".to_string()]).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_and_store_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("nova_secrets_{}", uuid::Uuid::new_v4()));
+
+        let mut store = SecretStore::open(&dir).unwrap();
+        store.set("TOKEN", "abc").unwrap();
+        store.set("TOKEN", "def").unwrap();
+
+        for file in [KEY_FILE, STORE_FILE] {
+            let mode = fs::metadata(dir.join(file)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file);
+        }
+        let leftovers: Vec<_> = fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("API_KEY").is_ok());
+        assert!(validate_name("1KEY").is_err());
+        assert!(validate_name("my-key").is_err());
+    }
+}
@@ -0,0 +1,636 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::core::secrets;
+use std::time::Duration;
+use wasm_encoder::{ExportKind, ExportSection, RawSection};
+use wasmparser::{Encoding, ExternalKind, Operator, Parser, Payload, TypeRef};
+
+const MAGIC: &[u8; 8] = b"NOVASNP1";
+pub const PAGE_SIZE: usize = 64 * 1024;
+const EXPORT_SECTION: u8 = 7;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Cap on the JSON header and, without a memory limit, on memory a snapshot
+/// may claim (all of a 32-bit address space).
+const MAX_HEADER_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_MEMORY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+pub fn global_export(index: u32) -> String {
+    format!("__nova_global_{}", index)
+}
+
+pub fn memory_export(index: u32) -> String {
+    format!("__nova_memory_{}", index)
+}
+
+pub fn table_export(index: u32) -> String {
+    format!("__nova_table_{}", index)
+}
+
+/// Number of globals, memories and tables (imported and defined) a module has.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StateLayout {
+    pub globals: u32,
+    pub memories: u32,
+    pub tables: u32,
+}
+
+/// Position of a section in the required module order (custom sections have none).
+fn section_rank(id: u8) -> Option<u8> {
+    match id {
+        1..=5 => Some(id),
+        13 => Some(6), // tag
+        6 => Some(7),
+        7 => Some(8),
+        8 => Some(9),
+        9 => Some(10),
+        12 => Some(11), // data count
+        10 => Some(12),
+        11 => Some(13),
+        _ => None,
+    }
+}
+
+fn export_kind(kind: ExternalKind) -> ExportKind {
+    match kind {
+        ExternalKind::Func => ExportKind::Func,
+        ExternalKind::Table => ExportKind::Table,
+        ExternalKind::Memory => ExportKind::Memory,
+        ExternalKind::Global => ExportKind::Global,
+        ExternalKind::Tag => ExportKind::Tag,
+    }
+}
+
+/// Export every global, memory and table under a `__nova_*` name so their
+/// state can be read and written from the host. Everything else is copied
+/// through unchanged.
+pub fn instrument(wasm: &[u8]) -> Result<(Vec<u8>, StateLayout)> {
+    rewrite(wasm, true)
+}
+
+/// `instrument`, also dropping the start section: a restored instance gets
+/// its state from the snapshot, and the start function already ran before
+/// the checkpoint was taken.
+pub fn instrument_for_restore(wasm: &[u8]) -> Result<(Vec<u8>, StateLayout)> {
+    rewrite(wasm, false)
+}
+
+fn rewrite(wasm: &[u8], keep_start: bool) -> Result<(Vec<u8>, StateLayout)> {
+    let mut layout = StateLayout::default();
+    let mut exports = ExportSection::new();
+    let mut sections: Vec<(u8, Range<usize>)> = Vec::new();
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        match &payload {
+            Payload::Version { encoding: Encoding::Component, .. } => {
+                anyhow::bail!("Wasm components are not supported, only core modules");
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader.clone() {
+                    match import?.ty {
+                        TypeRef::Global(_) => layout.globals += 1,
+                        TypeRef::Memory(_) => layout.memories += 1,
+                        TypeRef::Table(_) => layout.tables += 1,
+                        _ => {}
+                    }
+                }
+            }
+            Payload::GlobalSection(reader) => layout.globals += reader.count(),
+            Payload::MemorySection(reader) => layout.memories += reader.count(),
+            Payload::TableSection(reader) => layout.tables += reader.count(),
+            Payload::ExportSection(reader) => {
+                for export in reader.clone() {
+                    let export = export?;
+                    exports.export(export.name, export_kind(export.kind), export.index);
+                }
+                continue;
+            }
+            Payload::StartSection { .. } if !keep_start => continue,
+            _ => {}
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            sections.push((id, range));
+        }
+    }
+
+    for i in 0..layout.globals {
+        exports.export(&global_export(i), ExportKind::Global, i);
+    }
+    for i in 0..layout.memories {
+        exports.export(&memory_export(i), ExportKind::Memory, i);
+    }
+    for i in 0..layout.tables {
+        exports.export(&table_export(i), ExportKind::Table, i);
+    }
+
+    let export_rank = section_rank(EXPORT_SECTION);
+    let mut module = wasm_encoder::Module::new();
+    let mut exports = Some(exports);
+    for (id, range) in sections {
+        if section_rank(id) > export_rank {
+            if let Some(exports) = exports.take() {
+                module.section(&exports);
+            }
+        }
+        module.section(&RawSection { id, data: &wasm[range] });
+    }
+    if let Some(exports) = exports {
+        module.section(&exports);
+    }
+
+    Ok((module.finish(), layout))
+}
+
+/// Value of a mutable global. Immutable globals are restored by instantiation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GlobalValue {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    Immutable,
+}
+
+/// Size of a linear memory and which of its 64 KiB pages hold data.
+/// Only non-zero pages are stored in the snapshot file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryImage {
+    pub size: u64,
+    pub pages: Vec<u64>,
+}
+
+/// Everything `nova restore` needs besides memory contents. WASI descriptor
+/// state is not part of it: the restored instance gets fresh stdio and
+/// preopens, which is why modules that open descriptors themselves are
+/// refused by `check_checkpointable`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub module: String,
+    pub created_at: String,
+    pub container: String,
+    pub pid: u32,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// WASI preopens are reopened from the same host:guest mapping.
+    pub map_dir: Option<String>,
+    #[serde(default)]
+    pub volumes: Vec<(PathBuf, String)>,
+    /// Names only; restore resolves the values from the local secret store.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub secrets_dir: Option<String>,
+    pub memory_limit_mb: Option<u64>,
+    pub fuel: u64,
+    pub globals: Vec<GlobalValue>,
+    /// Table sizes; funcref contents come from the module's element segments.
+    pub tables: Vec<u64>,
+    pub memories: Vec<MemoryImage>,
+}
+
+/// A checkpointed instance: header plus the full contents of each memory.
+pub struct Snapshot {
+    pub header: SnapshotHeader,
+    pub memories: Vec<Vec<u8>>,
+}
+
+impl Snapshot {
+    pub fn layout(&self) -> StateLayout {
+        StateLayout {
+            globals: self.header.globals.len() as u32,
+            memories: self.memories.len() as u32,
+            tables: self.header.tables.len() as u32,
+        }
+    }
+
+    /// Write the snapshot, skipping all-zero pages. The file holds guest
+    /// memory, so it is only readable by the owner.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut header = self.header.clone();
+        header.memories = self.memories.iter()
+            .map(|data| MemoryImage {
+                size: data.len() as u64,
+                pages: data.chunks(PAGE_SIZE)
+                    .enumerate()
+                    .filter(|(_, page)| page.iter().any(|b| *b != 0))
+                    .map(|(i, _)| i as u64)
+                    .collect(),
+            })
+            .collect();
+
+        // Written next to `path` and renamed into place once complete
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = secrets::create_private(&tmp).with_context(|| format!("Failed to create snapshot {}", path.display()))?;
+
+        let mut out = BufWriter::new(file);
+        let json = serde_json::to_vec(&header)?;
+        out.write_all(MAGIC)?;
+        out.write_all(&(json.len() as u32).to_le_bytes())?;
+        out.write_all(&json)?;
+
+        for (data, image) in self.memories.iter().zip(&header.memories) {
+            for page in &image.pages {
+                let start = *page as usize * PAGE_SIZE;
+                let end = std::cmp::min(start + PAGE_SIZE, data.len());
+                out.write_all(&data[start..end])?;
+            }
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to write snapshot {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+        let mut input = BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            anyhow::bail!("{} is not a Nova snapshot", path.display());
+        }
+
+        let mut len = [0u8; 4];
+        input.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as u64;
+        if len > MAX_HEADER_BYTES {
+            anyhow::bail!("Snapshot header is corrupt ({} bytes)", len);
+        }
+        let mut json = vec![0u8; len as usize];
+        input.read_exact(&mut json)?;
+        let header: SnapshotHeader = serde_json::from_slice(&json).context("Snapshot header is corrupt")?;
+
+        // Sizes come from the file, so check them before allocating
+        let limit = header.memory_limit_mb.map(|mb| mb * 1024 * 1024).unwrap_or(MAX_MEMORY_BYTES);
+        let total = header.memories.iter().try_fold(0u64, |total, image| {
+            if image.size % PAGE_SIZE as u64 != 0 {
+                anyhow::bail!("Snapshot memory size {} is not a whole number of pages", image.size);
+            }
+            Ok(total.saturating_add(image.size))
+        })?;
+        if total > limit {
+            anyhow::bail!("Snapshot holds {} bytes of memory, more than the {} byte limit", total, limit);
+        }
+
+        let mut memories = Vec::new();
+        for image in &header.memories {
+            let mut data = vec![0u8; image.size as usize];
+            for page in &image.pages {
+                let start = *page as usize * PAGE_SIZE;
+                let end = std::cmp::min(start + PAGE_SIZE, data.len());
+                if start >= end {
+                    anyhow::bail!("Snapshot page {} is out of bounds", page);
+                }
+                input.read_exact(&mut data[start..end]).context("Snapshot is truncated")?;
+            }
+            memories.push(data);
+        }
+
+        Ok(Snapshot { header, memories })
+    }
+}
+
+/// Instance stopped after a checkpoint was written; not a guest failure.
+#[derive(Debug)]
+pub struct Checkpointed(pub PathBuf);
+
+impl std::fmt::Display for Checkpointed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "checkpointed to {}", self.0.display())
+    }
+}
+
+impl std::error::Error for Checkpointed {}
+
+/// Written by `nova checkpoint`, picked up by the instance at its next safe point.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointRequest {
+    pub output: PathBuf,
+    /// Stop the instance once the snapshot is written.
+    pub stop: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheckpointDone {
+    output: PathBuf,
+    error: Option<String>,
+}
+
+fn control_path(container: &str, pid: u32) -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(format!("{}/.nova/run/{}/{}", home, container.replace(':', "_").replace('/', "-"), pid))
+}
+
+/// Whether the process recorded in a control dir's `owner` file still runs.
+/// Without `/proc` to ask, the owner is assumed to be alive.
+fn owner_alive(dir: &Path) -> bool {
+    let Some(owner) = fs::read_to_string(dir.join("owner")).ok().and_then(|s| s.trim().parse::<u32>().ok()) else {
+        return true;
+    };
+    owner == std::process::id() || !Path::new("/proc/self").exists() || Path::new(&format!("/proc/{}", owner)).exists()
+}
+
+/// Whether `wasm` imports `env.nova_recv`, the safe point where checkpoints
+/// are taken and export health checks run.
+pub fn imports_recv(wasm: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::ImportSection(reader) = payload? {
+            for import in reader {
+                let import = import?;
+                if import.module == "env" && import.name == "nova_recv" {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Whether any function in `wasm` can change table contents or sizes.
+pub fn writes_tables(wasm: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CodeSectionEntry(body) = payload? {
+            for op in body.get_operators_reader()? {
+                if matches!(op?, Operator::TableSet { .. } | Operator::TableGrow { .. } | Operator::TableFill { .. }
+                    | Operator::TableCopy { .. } | Operator::TableInit { .. }) {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Fail if instances of `wasm` hold state a snapshot does not capture:
+/// funcref table entries written at run time, or WASI descriptors beyond
+/// stdio and preopens. Such modules would restore into a different state.
+pub fn check_checkpointable(wasm: &[u8]) -> Result<()> {
+    let mut problems = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::ImportSection(reader) = payload? {
+            for import in reader {
+                let import = import?;
+                if import.module == "wasi_snapshot_preview1" && matches!(import.name, "path_open" | "sock_accept") {
+                    problems.push(format!("it opens WASI descriptors ({}), which snapshots do not capture", import.name));
+                }
+            }
+        }
+    }
+    if writes_tables(wasm)? {
+        problems.push("it writes tables at run time, and snapshots only hold table sizes".to_string());
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("Module cannot be checkpointed: {}", problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Per-instance control directory `~/.nova/run/<container>/<pid>`, present
+/// while the instance runs.
+pub struct ControlDir {
+    path: PathBuf,
+}
+
+impl ControlDir {
+    /// Claim the control dir, failing while another instance with the same
+    /// container name and pid holds it. A dir left behind by a process that
+    /// no longer runs is taken over.
+    pub fn create(container: &str, pid: u32) -> Result<Self> {
+        let path = control_path(container, pid);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::create_dir(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                if owner_alive(&path) {
+                    anyhow::bail!(
+                        "Container '{}' already has a running instance with pid {} (remove {} if it does not)",
+                        container, pid, path.display()
+                    );
+                }
+                let _ = fs::remove_file(path.join("checkpoint.request"));
+                let _ = fs::remove_file(path.join("checkpoint.done"));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        fs::write(path.join("owner"), std::process::id().to_string())?;
+        Ok(ControlDir { path })
+    }
+
+    fn take_request(&self) -> Option<CheckpointRequest> {
+        let path = self.path.join("checkpoint.request");
+        let content = fs::read_to_string(&path).ok()?;
+        let _ = fs::remove_file(&path);
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Report the outcome of a checkpoint back to `nova checkpoint`.
+    pub fn complete(&self, output: &Path, result: &Result<()>) {
+        let done = CheckpointDone {
+            output: output.to_path_buf(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        };
+        if let Ok(json) = serde_json::to_string(&done) {
+            let _ = fs::write(self.path.join("checkpoint.done"), json);
+        }
+    }
+}
+
+impl Drop for ControlDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Resolves once a checkpoint is requested; never resolves without a control dir.
+pub async fn wait_for_request(control: Option<&ControlDir>) -> CheckpointRequest {
+    let Some(control) = control else {
+        return std::future::pending().await;
+    };
+    loop {
+        if let Some(request) = control.take_request() {
+            return request;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Ask a running instance for a checkpoint and wait until it is written.
+pub async fn request_checkpoint(container: &str, pid: u32, request: CheckpointRequest, timeout: Duration) -> Result<PathBuf> {
+    let dir = control_path(container, pid);
+    if !dir.exists() {
+        anyhow::bail!("No running instance '{}' with pid {}", container, pid);
+    }
+
+    let done_path = dir.join("checkpoint.done");
+    let _ = fs::remove_file(&done_path);
+    fs::write(dir.join("checkpoint.request"), serde_json::to_string(&request)?)?;
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(content) = fs::read_to_string(&done_path) {
+            let done: CheckpointDone = serde_json::from_str(&content)?;
+            let _ = fs::remove_file(&done_path);
+            return match done.error {
+                Some(e) => anyhow::bail!("Checkpoint failed: {}", e),
+                None => Ok(done.output),
+            };
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    let _ = fs::remove_file(dir.join("checkpoint.request"));
+    anyhow::bail!(
+        "Instance '{}' did not reach a safe point within {}s (checkpoints are taken while it waits in nova_recv)",
+        container,
+        timeout.as_secs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instrument_exports_state() {
+        let wasm = wat::parse_str(r#"
+            (module
+              (import "env" "g" (global i32))
+              (memory 1)
+              (global (mut i32) (i32.const 7))
+              (table 2 funcref)
+              (func (export "run"))
+              (data (i32.const 0) "hi"))
+        "#).unwrap();
+
+        let (instrumented, layout) = instrument(&wasm).unwrap();
+        assert_eq!(layout, StateLayout { globals: 2, memories: 1, tables: 1 });
+
+        let mut names = Vec::new();
+        for payload in Parser::new(0).parse_all(&instrumented) {
+            if let Payload::ExportSection(reader) = payload.unwrap() {
+                for export in reader {
+                    names.push(export.unwrap().name.to_string());
+                }
+            }
+        }
+        assert_eq!(names, vec!["run", "__nova_global_0", "__nova_global_1", "__nova_memory_0", "__nova_table_0"]);
+        wasmparser::validate(&instrumented).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let path = std::env::temp_dir().join(format!("nova_snapshot_{}.snap", uuid::Uuid::new_v4()));
+        let mut memory = vec![0u8; 3 * PAGE_SIZE];
+        memory[PAGE_SIZE + 5] = 42;
+
+        let snapshot = Snapshot {
+            header: SnapshotHeader {
+                module: "app.wasm".to_string(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                container: "app".to_string(),
+                pid: 0,
+                args: Vec::new(),
+                env: Vec::new(),
+                map_dir: None,
+                volumes: Vec::new(),
+                secrets: Vec::new(),
+                secrets_dir: None,
+                memory_limit_mb: None,
+                fuel: 1000,
+                globals: vec![GlobalValue::Immutable, GlobalValue::I32(7)],
+                tables: vec![2],
+                memories: Vec::new(),
+            },
+            memories: vec![memory.clone()],
+        };
+        snapshot.write(&path).unwrap();
+
+        // Only the non-zero page is stored, in a file only the owner can read
+        assert!(fs::metadata(&path).unwrap().len() < 2 * PAGE_SIZE as u64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let restored = Snapshot::read(&path).unwrap();
+        assert_eq!(restored.memories, vec![memory]);
+        assert_eq!(restored.header.memories[0].pages, vec![1]);
+        assert_eq!(restored.header.globals[1], GlobalValue::I32(7));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_restore_drops_start() {
+        let wasm = wat::parse_str(r#"(module (func $init) (start $init) (func (export "nova_resume")))"#).unwrap();
+        let has_start = |wasm: &[u8]| Parser::new(0).parse_all(wasm)
+            .any(|payload| matches!(payload.unwrap(), Payload::StartSection { .. }));
+
+        assert!(has_start(&instrument(&wasm).unwrap().0));
+        let (restored, _) = instrument_for_restore(&wasm).unwrap();
+        assert!(!has_start(&restored));
+        wasmparser::validate(&restored).unwrap();
+    }
+
+    #[test]
+    fn test_check_checkpointable() {
+        let plain = wat::parse_str(r#"(module (table 1 funcref) (func (export "_start") (drop (table.size 0))))"#).unwrap();
+        assert!(check_checkpointable(&plain).is_ok());
+
+        let grows = wat::parse_str(r#"(module (table 1 funcref) (func (drop (table.grow 0 (ref.null func) (i32.const 1)))))"#).unwrap();
+        assert!(check_checkpointable(&grows).unwrap_err().to_string().contains("tables"));
+
+        let opens = wat::parse_str(r#"(module (import "wasi_snapshot_preview1" "path_open"
+            (func (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32))))"#).unwrap();
+        assert!(check_checkpointable(&opens).unwrap_err().to_string().contains("path_open"));
+    }
+
+    #[test]
+    fn test_read_checks_sizes_before_allocating() {
+        let path = std::env::temp_dir().join(format!("nova_snapshot_{}.snap", uuid::Uuid::new_v4()));
+        let header = serde_json::json!({
+            "module": "app.wasm", "created_at": "", "container": "app", "pid": 0, "args": [], "env": [],
+            "map_dir": null, "memory_limit_mb": 1, "fuel": 0, "globals": [], "tables": [],
+            "memories": [{ "size": 1u64 << 40, "pages": [] }],
+        });
+        let json = serde_json::to_vec(&header).unwrap();
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        file.extend_from_slice(&json);
+        fs::write(&path, file).unwrap();
+
+        let result = Snapshot::read(&path);
+        let _ = fs::remove_file(&path);
+        assert!(result.err().unwrap().to_string().contains("limit"));
+    }
+
+    #[test]
+    fn test_control_dir_is_claimed_once() {
+        let container = format!("control-{}", uuid::Uuid::new_v4());
+        let control = ControlDir::create(&container, 0).unwrap();
+        assert!(ControlDir::create(&container, 0).is_err());
+        assert!(ControlDir::create(&container, 1).is_ok());
+
+        drop(control);
+        let _ = ControlDir::create(&container, 0).unwrap();
+        let _ = fs::remove_dir_all(control_path(&container, 0).parent().unwrap());
+    }
+
+    #[test]
+    fn test_imports_recv() {
+        let receiver = wat::parse_str(r#"(module (import "env" "nova_recv" (func (param i32 i32) (result i32))))"#).unwrap();
+        assert!(imports_recv(&receiver).unwrap());
+        assert!(!imports_recv(&wat::parse_str("(module)").unwrap()).unwrap());
+    }
+}
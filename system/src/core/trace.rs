@@ -0,0 +1,596 @@
+use anyhow::{Context, Result};
+use cap_rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::core::http::HttpPolicy;
+use crate::core::secrets;
+use wasmtime_wasi::preview2::{HostMonotonicClock, HostOutputStream, HostWallClock, StdoutStream, StreamResult, Subscribe};
+
+const MAGIC: &[u8; 8] = b"NOVATRC1";
+
+/// Byte payloads are stored as base64 to keep trace frames compact.
+mod b64 {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        base64::engine::general_purpose::STANDARD.decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// What a trace was recorded from, including the limits and modes the run
+/// had, so a replay runs out of fuel or memory where the recording did.
+/// Only secret names are stored; replay resolves their values from the
+/// local secret store again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceHeader {
+    pub module: String,
+    pub pid: u32,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub secrets_dir: Option<String>,
+    pub fuel: u64,
+    pub memory_limit_mb: Option<u64>,
+    pub map_dir: Option<String>,
+    #[serde(default)]
+    pub volumes: Vec<(PathBuf, String)>,
+    #[serde(default)]
+    pub http: HttpPolicy,
+    /// Seed of a deterministic run.
+    pub deterministic: Option<u64>,
+    pub recorded_at: String,
+}
+
+/// One host-boundary event. Values flowing into the guest (clocks,
+/// randomness, messages, HTTP responses) are fed back on replay; guest
+/// effects (sends, logs, stdout/stderr writes, exit) are compared against
+/// the recording.
+///
+/// HTTP URLs are stored without userinfo, query or fragment, like the
+/// egress log. Response bodies and received messages are stored unmasked
+/// because replay feeds them back to the guest.
+///
+/// Stdin and files in mounted directories are not recorded. A guest that
+/// reads different data from them on replay shows up as a divergence at
+/// its first differing effect, not as a silent pass.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    WallClock { nanos: u64 },
+    MonotonicClock { nanos: u64 },
+    Random { #[serde(with = "b64")] bytes: Vec<u8> },
+    ClockMs { millis: u64 },
+    Pid { pid: u32 },
+    Recv { #[serde(with = "b64")] payload: Vec<u8>, closed: bool },
+    Send { target: u32, #[serde(with = "b64")] payload: Vec<u8> },
+    Log { level: String, message: String },
+    Write { fd: u32, #[serde(with = "b64")] bytes: Vec<u8> },
+    HttpFetch { method: String, url: String, status: i32, #[serde(with = "b64")] body: Vec<u8> },
+    Exit { error: Option<String> },
+}
+
+impl TraceEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TraceEvent::WallClock { .. } => "wall_clock",
+            TraceEvent::MonotonicClock { .. } => "monotonic_clock",
+            TraceEvent::Random { .. } => "random",
+            TraceEvent::ClockMs { .. } => "clock_ms",
+            TraceEvent::Pid { .. } => "pid",
+            TraceEvent::Recv { .. } => "recv",
+            TraceEvent::Send { .. } => "send",
+            TraceEvent::Log { .. } => "log",
+            TraceEvent::Write { .. } => "write",
+            TraceEvent::HttpFetch { .. } => "http_fetch",
+            TraceEvent::Exit { .. } => "exit",
+        }
+    }
+
+    /// Whether a replayed input can stand in for `live`: same kind, and for
+    /// requests that carry a shape (random length, HTTP target) the same shape.
+    fn matches_input(&self, live: &TraceEvent) -> bool {
+        match (self, live) {
+            (TraceEvent::Random { bytes: a }, TraceEvent::Random { bytes: b }) => a.len() == b.len(),
+            (TraceEvent::HttpFetch { method: m1, url: u1, .. }, TraceEvent::HttpFetch { method: m2, url: u2, .. }) => m1 == m2 && u1 == u2,
+            _ => self.kind() == live.kind(),
+        }
+    }
+}
+
+impl std::fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = |bytes: &[u8]| {
+            let s = String::from_utf8_lossy(bytes);
+            if s.chars().count() > 64 {
+                format!("{:?}…", s.chars().take(64).collect::<String>())
+            } else {
+                format!("{:?}", s)
+            }
+        };
+        match self {
+            TraceEvent::WallClock { nanos } => write!(f, "wall_clock({})", nanos),
+            TraceEvent::MonotonicClock { nanos } => write!(f, "monotonic_clock({})", nanos),
+            TraceEvent::Random { bytes } => write!(f, "random({} bytes)", bytes.len()),
+            TraceEvent::ClockMs { millis } => write!(f, "clock_ms({})", millis),
+            TraceEvent::Pid { pid } => write!(f, "pid({})", pid),
+            TraceEvent::Recv { payload, closed } => if *closed { write!(f, "recv(closed)") } else { write!(f, "recv({})", text(payload)) },
+            TraceEvent::Send { target, payload } => write!(f, "send(pid {}, {})", target, text(payload)),
+            TraceEvent::Log { level, message } => write!(f, "log({}, {:?})", level, message),
+            TraceEvent::Write { fd, bytes } => write!(f, "write(fd {}, {})", fd, text(bytes)),
+            TraceEvent::HttpFetch { method, url, status, .. } => write!(f, "http_fetch({} {} -> {})", method, url, status),
+            TraceEvent::Exit { error: None } => write!(f, "exit(ok)"),
+            TraceEvent::Exit { error: Some(e) } => write!(f, "exit({})", e),
+        }
+    }
+}
+
+/// First point where a replayed run stopped matching its recording.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<TraceEvent>,
+    pub actual: Option<TraceEvent>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |e: &Option<TraceEvent>| e.as_ref().map(|e| e.to_string()).unwrap_or_else(|| "end of run".to_string());
+        write!(f, "event #{}: expected {}, got {}", self.index, show(&self.expected), show(&self.actual))
+    }
+}
+
+enum Mode {
+    Record(BufWriter<File>),
+    Replay(VecDeque<TraceEvent>),
+}
+
+struct TraceState {
+    mode: Mode,
+    events: usize,
+    divergence: Option<Divergence>,
+    error: Option<String>,
+}
+
+/// Shared recorder/replayer for one instance's host-boundary events.
+#[derive(Clone)]
+pub struct TraceHandle(Arc<Mutex<TraceState>>);
+
+impl TraceHandle {
+    /// Start recording into `path`. Recorded inputs such as HTTP response
+    /// bodies and received messages are stored as the guest saw them, so the
+    /// file is created readable by its owner only.
+    pub fn record(path: &Path, header: &TraceHeader) -> Result<Self> {
+        let file = secrets::create_private(path).with_context(|| format!("Failed to create trace {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC)?;
+        write_frame(&mut out, header)?;
+
+        Ok(Self::with_mode(Mode::Record(out)))
+    }
+
+    /// Load a recording for replay.
+    pub fn replay(path: &Path) -> Result<(Self, TraceHeader)> {
+        let file = File::open(path).with_context(|| format!("Failed to open trace {}", path.display()))?;
+        let mut input = BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            anyhow::bail!("{} is not a Nova trace file", path.display());
+        }
+
+        let header: TraceHeader = read_frame(&mut input)?
+            .ok_or_else(|| anyhow::anyhow!("Trace {} has no header", path.display()))?;
+        let mut events = VecDeque::new();
+        while let Some(event) = read_frame(&mut input)? {
+            events.push_back(event);
+        }
+
+        Ok((Self::with_mode(Mode::Replay(events)), header))
+    }
+
+    fn with_mode(mode: Mode) -> Self {
+        TraceHandle(Arc::new(Mutex::new(TraceState { mode, events: 0, divergence: None, error: None })))
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.0.lock().unwrap().mode, Mode::Replay(_))
+    }
+
+    /// A value flowing into the guest. Recording stores `live`; replay
+    /// returns the recorded event instead, or `live` once the run diverged.
+    pub fn input(&self, live: TraceEvent) -> TraceEvent {
+        let mut guard = self.0.lock().unwrap();
+        let state = &mut *guard;
+        let index = state.events;
+        state.events += 1;
+
+        match &mut state.mode {
+            Mode::Record(out) => {
+                if let Err(e) = write_frame(out, &live) {
+                    state.error.get_or_insert(e.to_string());
+                }
+                live
+            }
+            Mode::Replay(events) => {
+                if state.divergence.is_some() {
+                    return live;
+                }
+                match events.pop_front() {
+                    Some(recorded) if recorded.matches_input(&live) => recorded,
+                    expected => {
+                        state.divergence = Some(Divergence { index, expected, actual: Some(live.clone()) });
+                        live
+                    }
+                }
+            }
+        }
+    }
+
+    /// An effect of the guest. Recording stores it; replay compares it
+    /// against the recording.
+    pub fn output(&self, event: TraceEvent) {
+        let mut guard = self.0.lock().unwrap();
+        let state = &mut *guard;
+        let index = state.events;
+        state.events += 1;
+
+        match &mut state.mode {
+            Mode::Record(out) => {
+                if let Err(e) = write_frame(out, &event) {
+                    state.error.get_or_insert(e.to_string());
+                }
+            }
+            Mode::Replay(events) => {
+                if state.divergence.is_some() {
+                    return;
+                }
+                let expected = events.pop_front();
+                if expected.as_ref() != Some(&event) {
+                    state.divergence = Some(Divergence { index, expected, actual: Some(event) });
+                }
+            }
+        }
+    }
+
+    /// Flush a recording, or check that a replay consumed the whole trace.
+    pub fn finish(&self) -> Result<()> {
+        let mut guard = self.0.lock().unwrap();
+        let state = &mut *guard;
+        if let Some(e) = state.error.take() {
+            anyhow::bail!("Failed to write trace: {}", e);
+        }
+
+        let index = state.events;
+        match &mut state.mode {
+            Mode::Record(out) => out.flush()?,
+            Mode::Replay(events) => {
+                if state.divergence.is_none() {
+                    if let Some(expected) = events.pop_front() {
+                        state.divergence = Some(Divergence { index, expected: Some(expected), actual: None });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of events seen and the first divergence, if any.
+    pub fn report(&self) -> (usize, Option<Divergence>) {
+        let state = self.0.lock().unwrap();
+        (state.events, state.divergence.clone())
+    }
+}
+
+fn write_frame<T: Serialize>(out: &mut impl Write, value: &T) -> Result<()> {
+    let bytes = serde_json::to_vec(value)?;
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_frame<T: for<'de> Deserialize<'de>>(input: &mut impl Read) -> Result<Option<T>> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut bytes).context("Trace file is truncated")?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+/// Host system time, wrapped when tracing a non-deterministic run.
+pub struct SystemWallClock;
+
+impl HostWallClock for SystemWallClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+pub struct SystemMonotonicClock {
+    start: std::time::Instant,
+}
+
+impl Default for SystemMonotonicClock {
+    fn default() -> Self {
+        Self { start: std::time::Instant::now() }
+    }
+}
+
+impl HostMonotonicClock for SystemMonotonicClock {
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+}
+
+/// WASI wall clock passing through the trace.
+pub struct TracedWallClock {
+    pub inner: Box<dyn HostWallClock>,
+    pub trace: TraceHandle,
+}
+
+impl HostWallClock for TracedWallClock {
+    fn resolution(&self) -> Duration {
+        self.inner.resolution()
+    }
+
+    fn now(&self) -> Duration {
+        let live = TraceEvent::WallClock { nanos: self.inner.now().as_nanos() as u64 };
+        match self.trace.input(live) {
+            TraceEvent::WallClock { nanos } => Duration::from_nanos(nanos),
+            _ => self.inner.now(),
+        }
+    }
+}
+
+/// WASI monotonic clock passing through the trace.
+pub struct TracedMonotonicClock {
+    pub inner: Box<dyn HostMonotonicClock>,
+    pub trace: TraceHandle,
+}
+
+impl HostMonotonicClock for TracedMonotonicClock {
+    fn resolution(&self) -> u64 {
+        self.inner.resolution()
+    }
+
+    fn now(&self) -> u64 {
+        let live = TraceEvent::MonotonicClock { nanos: self.inner.now() };
+        match self.trace.input(live) {
+            TraceEvent::MonotonicClock { nanos } => nanos,
+            _ => self.inner.now(),
+        }
+    }
+}
+
+/// WASI random source passing through the trace.
+pub struct TracedRng {
+    pub inner: Box<dyn RngCore + Send + Sync>,
+    pub trace: TraceHandle,
+}
+
+impl RngCore for TracedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        if let TraceEvent::Random { bytes } = self.trace.input(TraceEvent::Random { bytes: dest.to_vec() }) {
+            dest.copy_from_slice(&bytes);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), cap_rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Guest stdout (fd 1) or stderr (fd 2), passed through to the host's and
+/// recorded as `Write` events with `secrets` masked.
+pub struct TracedStdout {
+    pub fd: u32,
+    pub secrets: Arc<Vec<String>>,
+    pub trace: TraceHandle,
+}
+
+impl StdoutStream for TracedStdout {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        let inner = match self.fd {
+            1 => wasmtime_wasi::preview2::stdout().stream(),
+            _ => wasmtime_wasi::preview2::stderr().stream(),
+        };
+        Box::new(TracedOutputStream { inner, fd: self.fd, secrets: self.secrets.clone(), trace: self.trace.clone() })
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+struct TracedOutputStream {
+    inner: Box<dyn HostOutputStream>,
+    fd: u32,
+    secrets: Arc<Vec<String>>,
+    trace: TraceHandle,
+}
+
+/// `bytes` with every occurrence of a secret replaced by `***`. Only whole
+/// occurrences within one write are caught.
+fn mask(bytes: &[u8], secrets: &[String]) -> Vec<u8> {
+    let mut out = bytes.to_vec();
+    for secret in secrets.iter().map(String::as_bytes).filter(|secret| !secret.is_empty()) {
+        let mut masked = Vec::with_capacity(out.len());
+        let mut rest = &out[..];
+        while let Some(at) = rest.windows(secret.len()).position(|window| window == secret) {
+            masked.extend_from_slice(&rest[..at]);
+            masked.extend_from_slice(b"***");
+            rest = &rest[at + secret.len()..];
+        }
+        masked.extend_from_slice(rest);
+        out = masked;
+    }
+    out
+}
+
+#[async_trait::async_trait]
+impl Subscribe for TracedOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+impl HostOutputStream for TracedOutputStream {
+    fn write(&mut self, bytes: bytes::Bytes) -> StreamResult<()> {
+        self.trace.output(TraceEvent::Write { fd: self.fd, bytes: mask(&bytes, &self.secrets) });
+        self.inner.write(bytes)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.inner.check_write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!("nova_trace_{}.bin", uuid::Uuid::new_v4()));
+        let header = TraceHeader {
+            module: "app.wasm".to_string(),
+            pid: 0,
+            args: Vec::new(),
+            env: Vec::new(),
+            secrets: Vec::new(),
+            secrets_dir: None,
+            fuel: u64::MAX,
+            memory_limit_mb: None,
+            map_dir: None,
+            volumes: Vec::new(),
+            http: HttpPolicy::default(),
+            deterministic: None,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let trace = TraceHandle::record(&path, &header).unwrap();
+        trace.input(TraceEvent::ClockMs { millis: 42 });
+        trace.output(TraceEvent::Log { level: "INFO".to_string(), message: "hi".to_string() });
+        trace.finish().unwrap();
+
+        // Recorded inputs come back in place of live values
+        let (trace, header) = TraceHandle::replay(&path).unwrap();
+        assert_eq!(header.module, "app.wasm");
+        assert_eq!(trace.input(TraceEvent::ClockMs { millis: 7 }), TraceEvent::ClockMs { millis: 42 });
+
+        // A different guest effect is reported as the first divergence
+        trace.output(TraceEvent::Log { level: "INFO".to_string(), message: "bye".to_string() });
+        trace.finish().unwrap();
+        let (_, divergence) = trace.report();
+        assert_eq!(divergence.unwrap().index, 1);
+
+        assert_eq!(mask(b"token=s3cret s3cret", &["s3cret".to_string()]), b"token=*** ***");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_replay_compares_stdout() {
+        let guest = |text: &str| format!(r#"(module
+            (import "wasi_snapshot_preview1" "fd_write" (func $write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\10\00\00\00\{:02x}\00\00\00")
+            (data (i32.const 16) "{}")
+            (func (export "_start") (drop (call $write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#, text.len(), text);
+        let dir = std::env::temp_dir().join(format!("nova_trace_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (recorded, changed, trace_path) = (dir.join("hi.wat"), dir.join("bye.wat"), dir.join("run.trace"));
+        std::fs::write(&recorded, guest("hi")).unwrap();
+        std::fs::write(&changed, guest("bye")).unwrap();
+
+        let replay = |module: std::path::PathBuf| {
+            let trace_path = trace_path.clone();
+            async move {
+                let (trace, _) = TraceHandle::replay(&trace_path).unwrap();
+                let mut config = crate::core::runtime::RunConfig::new("trace-test");
+                config.trace = Some(trace.clone());
+                crate::core::runtime::run_wasm(&module.to_string_lossy(), config).await.unwrap();
+                trace.finish().unwrap();
+                trace.report().1
+            }
+        };
+
+        let trace = TraceHandle::record(&trace_path, &TraceHeader {
+            module: recorded.to_string_lossy().to_string(),
+            pid: 0,
+            args: Vec::new(),
+            env: Vec::new(),
+            secrets: Vec::new(),
+            secrets_dir: None,
+            fuel: u64::MAX,
+            memory_limit_mb: None,
+            map_dir: None,
+            volumes: Vec::new(),
+            http: HttpPolicy::default(),
+            deterministic: None,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        }).unwrap();
+        let mut config = crate::core::runtime::RunConfig::new("trace-test");
+        config.trace = Some(trace.clone());
+        crate::core::runtime::run_wasm(&recorded.to_string_lossy(), config).await.unwrap();
+        trace.finish().unwrap();
+
+        assert!(replay(recorded.clone()).await.is_none());
+        let divergence = replay(changed.clone()).await.unwrap();
+        assert_eq!(divergence.actual, Some(TraceEvent::Write { fd: 1, bytes: b"bye".to_vec() }));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,29 @@
+//! Nova runs WebAssembly containers on Wasmtime, builds images from
+//! Novafiles and serves the cloud dashboard API. The `nova` binary is a thin
+//! CLI over this crate; services can embed the same runtime directly:
+//!
+//! ```no_run
+//! use nova::{run_wasm, RunConfig};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut config = RunConfig::new("worker");
+//! config.fuel = 1_000_000_000;
+//! config.memory_limit_mb = Some(64);
+//! config.args = vec!["--mode".to_string(), "batch".to_string()];
+//! run_wasm("worker.wasm", config).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Replicas that talk over the in-process mesh are started with
+//! [`Scheduler::run_replicas`]; custom imports are added by implementing
+//! [`HostModule`] and pushing it onto [`RunConfig::host_modules`].
+
+pub mod core;
+pub mod api;
+
+pub use crate::core::builder::{Image, ImageBuilder, ImageMetadata, Novafile};
+pub use crate::core::host::HostModule;
+pub use crate::core::runtime::{run_wasm, NovaState, RunConfig};
+pub use crate::core::runtime_detector::{Language, RuntimeDetector};
+pub use crate::core::scheduler::Scheduler;
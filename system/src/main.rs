@@ -1,5 +1,3 @@
-mod core;
-mod api;
 mod cli;
 
 use anyhow::Result;
@@ -4,7 +4,7 @@
 
 set -e  # Exit on error
 
-NOVA_BIN="./target/release/nova_container_system"
+NOVA_BIN="./target/release/nova"
 TEST_DIR="./test_apps"
 
 echo "🧪 Nova Runtime Test Suite"